@@ -70,6 +70,8 @@ fn setup(
         })
         .render(ParticleTextureModifier {
             texture: texture_handle.clone(),
+            texture_path: Some("cloud.png".to_string()),
+            ..Default::default()
         })
         .render(ColorOverLifetimeModifier { gradient })
         .render(SizeOverLifetimeModifier {
@@ -80,6 +80,8 @@ fn setup(
         }
         .render(ParticleTextureModifier {
             texture: texture_handle.clone(),
+            texture_path: Some("cloud.png".to_string()),
+            ..Default::default()
         }).render(SizeOverLifetimeModifier{
             gradient: size_gradient,
         })
@@ -103,6 +105,8 @@ fn setup(
         }
         .render(ParticleTextureModifier {
             texture: texture_handle.clone(),
+            texture_path: Some("cloud.png".to_string()),
+            ..Default::default()
         })
         .render(ColorOverLifetimeModifier { gradient }),
     );
@@ -1,30 +1,112 @@
 use bevy::{
-    asset::{AssetLoader, Handle, LoadContext, LoadedAsset},
+    asset::{AssetLoader, AssetPath, Handle, LoadContext, LoadedAsset},
     math::{Vec2, Vec3, Vec4},
     reflect::TypeUuid,
-    render::texture::Image,
+    render::{mesh::Mesh, texture::Image},
     utils::BoxedFuture,
 };
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    modifiers::{ForceFieldParam, FFNUM},
-    Gradient, InitModifier, RenderModifier, Spawner, UpdateModifier, gradient::GradientEnum,
+    gradient::{GradientEnum, Lerp},
+    modifiers::{
+        BillboardMode, CollisionResponse, CustomModifier, FlipbookMode, ForceFieldParam, Modifier,
+        TextureMode, FFNUM,
+    },
+    render::particle_item_size,
+    ron_merge::RonNode,
+    CurlNoiseModifier, FlipbookModifier, Gradient, InitModifier, RenderModifier, Spawner,
+    UpdateModifier, Value,
 };
 
 #[derive(Default, Clone)]
 pub struct InitLayout {
+    /// Code initializing the position and velocity of a newly emitted particle. Made of the
+    /// contributions of all position modifiers, in order. See [`add_position_code()`].
+    ///
+    /// [`add_position_code()`]: InitLayout::add_position_code
     pub position_code: String,
+    /// Code overriding the initial velocity set by the position code, if not empty. Made of the
+    /// contributions of all velocity modifiers, in order. See [`add_velocity_code()`].
+    ///
+    /// [`add_velocity_code()`]: InitLayout::add_velocity_code
+    pub velocity_code: String,
+    /// Code returning the lifetime of a newly emitted particle, if not empty.
+    pub lifetime_code: String,
+    /// Code returning the rotation and angular velocity of a newly emitted particle, if not
+    /// empty.
+    pub rotation_code: String,
     pub force_field_code: String,
+    /// Mesh to spawn the particles on, if any.
+    pub mesh_surface: Option<Handle<Mesh>>,
+}
+
+impl InitLayout {
+    /// Append the code of a position modifier.
+    ///
+    /// The code runs in its own scope, with `ret` reset to a zero position and velocity, and
+    /// its result is added to the position and velocity produced by the previous position
+    /// modifiers. This allows stacking shapes, for example a small sphere on top of a circle
+    /// to spawn particles inside a torus.
+    pub fn add_position_code(&mut self, code: &str) {
+        self.position_code += &format!(
+            r##"
+    {{
+    let prev = ret;
+    ret.pos = vec3<f32>(0., 0., 0.);
+    ret.vel = vec3<f32>(0., 0., 0.);
+{}
+    ret.pos = prev.pos + ret.pos;
+    ret.vel = prev.vel + ret.vel;
+    }}
+"##,
+            code
+        );
+    }
+
+    /// Append the code of a velocity modifier.
+    ///
+    /// The code runs in its own scope, with `ret.vel` reset to zero, and its result is added
+    /// to the velocity produced by the previous velocity modifiers. The velocity set by the
+    /// position modifiers is discarded as soon as any velocity modifier is present.
+    pub fn add_velocity_code(&mut self, code: &str) {
+        self.velocity_code += &format!(
+            r##"
+    {{
+    let prev_vel = ret.vel;
+    ret.vel = vec3<f32>(0., 0., 0.);
+{}
+    ret.vel = prev_vel + ret.vel;
+    }}
+"##,
+            code
+        );
+    }
 }
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone)]
 pub struct UpdateLayout {
     /// Constant accelereation to apply to all particles.
     /// Generally used to simulate some kind of gravity.
     pub accel: Vec3,
     /// Array of force field components with a maximum number of components determined by [`FFNUM`].
     pub force_field: [ForceFieldParam; FFNUM],
+    /// Code adding the accelerations of the update modifiers to `modifiers_accel`, integrated
+    /// alongside [`accel`] each frame. Made of the contributions of all those modifiers, in order.
+    ///
+    /// [`accel`]: UpdateLayout::accel
+    pub accel_code: String,
+    /// Code constraining the position and velocity of the particles after their integration
+    /// each frame, or killing them by setting `vAge` to `vLifetime`. Made of the contributions
+    /// of all those modifiers, in order.
+    pub post_update_code: String,
+    /// Child effect spawned where particles die, and number of child particles per death.
+    pub sub_emitter: Option<(Handle<EffectAsset>, u32)>,
 }
 
 #[derive(Default, Clone)]
@@ -33,20 +115,50 @@ pub struct RenderLayout {
     /// UV coordinates. Also make available the image as a 2D texture and sampler in the render
     /// shaders.
     pub particle_texture: Option<Handle<Image>>,
+    /// How the particle texture is combined with the particle color.
+    pub texture_mode: TextureMode,
+
+    /// Color gradients over the particle lifetime. The colors of all gradients are multiplied
+    /// together, in order.
+    pub lifetime_color_gradients: Vec<GradientEnum<Vec4>>,
+    /// Constant color multiplied with the color of the particles, if any.
+    pub color_tint: Option<Vec4>,
+
+    /// Size gradients over the particle lifetime. The sizes of all gradients are multiplied
+    /// together, in order.
+    pub size_gradients: Vec<Gradient<Vec2>>,
+
+    /// Rotation gradients over the particle lifetime. The rotations of all gradients are added
+    /// to the rotation of the particles.
+    pub rotation_gradients: Vec<Gradient<f32>>,
 
-    pub lifetime_color_gradient: Option<GradientEnum<Vec4>>,
-    // pub lifetime_color_gradient: Option<Gradient<Vec4>>,
+    /// If set, animate the particle texture as a sprite sheet. See [`FlipbookModifier`].
+    pub flipbook: Option<FlipbookModifier>,
 
-    pub size_color_gradient: Option<Gradient<Vec2>>,
+    /// Orientation of the particle quads. See [`BillboardModifier`].
+    ///
+    /// [`BillboardModifier`]: crate::BillboardModifier
+    pub billboard: BillboardMode,
+
+    /// If set, align the particles along their velocity, with the given stretch factor. See
+    /// [`OrientAlongVelocityModifier`].
+    ///
+    /// [`OrientAlongVelocityModifier`]: crate::OrientAlongVelocityModifier
+    pub orient_along_velocity: Option<f32>,
 }
 
 /// Asset describing a visual effect.
 ///
 /// The effect can be instanciated with a [`ParticleEffect`] component, or a [`ParticleEffectBundle`].
 ///
+/// The effect keeps the list of [`Modifier`]s it was built with, in the order they were added.
+/// The init, update, and render layouts used to generate the shaders of the effect are derived
+/// on demand from that list, so modifiers can be inspected, reordered, removed, or edited at
+/// any time.
+///
 /// [`ParticleEffect`]: crate::ParticleEffect
 /// [`ParticleEffectBundle`]: crate::ParticleEffectBundle
-#[derive(Default, Serialize, Deserialize, TypeUuid)]
+#[derive(Default, Clone, Serialize, Deserialize, TypeUuid)]
 #[uuid = "249aefa4-9b8e-48d3-b167-3adf6c081c34"]
 pub struct EffectAsset {
     /// Display name of the effect.
@@ -55,65 +167,877 @@ pub struct EffectAsset {
     pub capacity: u32,
     /// Spawner.
     pub spawner: Spawner,
-
-    // pub particleAppearAreaBuffer
-    ///
-    #[serde(skip)] // TODO
-    pub init_layout: InitLayout,
-    ///
-    #[serde(skip)] // TODO
-    pub update_layout: UpdateLayout,
-    ///
-    #[serde(skip)] // TODO
-    pub render_layout: RenderLayout,
+    /// Modifiers defining the effect, in the order they are applied.
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
 }
-///
-//#[serde(skip)] // TODO
-//modifiers: Vec<Box<dyn Modifier + Send + Sync + 'static>>,
 
 impl EffectAsset {
     /// Add an initialization modifier to the effect.
-    pub fn init<M: InitModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
-        modifier.apply(&mut self.init_layout);
-        //self.modifiers.push(Box::new(modifier));
+    pub fn init<M: InitModifier + Into<Modifier>>(mut self, modifier: M) -> Self {
+        self.modifiers.push(modifier.into());
         self
     }
-    
+
     /// dynimally init modifier of the effect.
-    pub fn init_mut_ref<M: InitModifier + Send + Sync + 'static>(&mut self, modifier: M) -> &mut Self {
-        modifier.apply(&mut self.init_layout);
-        //self.modifiers.push(Box::new(modifier));
+    pub fn init_mut_ref<M: InitModifier + Into<Modifier>>(&mut self, modifier: M) -> &mut Self {
+        self.modifiers.push(modifier.into());
         self
     }
 
     /// Add an update modifier to the effect.
-    pub fn update<M: UpdateModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
-        modifier.apply(&mut self.update_layout);
-        //self.modifiers.push(Box::new(modifier));
+    pub fn update<M: UpdateModifier + Into<Modifier>>(mut self, modifier: M) -> Self {
+        self.modifiers.push(modifier.into());
         self
     }
 
     /// dynimally update modifier of the effect.
-    pub fn update_mut_ref<M: UpdateModifier + Send + Sync + 'static>(&mut self, modifier: M)->&mut Self {
-        modifier.apply(&mut self.update_layout); 
-        self       
+    pub fn update_mut_ref<M: UpdateModifier + Into<Modifier>>(&mut self, modifier: M) -> &mut Self {
+        self.modifiers.push(modifier.into());
+        self
     }
 
-     /// dynimally update  render modifier of the effect.
-    pub fn render_mut_ref<M: RenderModifier + Send + Sync + 'static>(&mut self, modifier: M) -> &mut Self {
-        modifier.apply(&mut self.render_layout);
-        //self.modifiers.push(Box::new(modifier));
+    /// dynimally update  render modifier of the effect.
+    pub fn render_mut_ref<M: RenderModifier + Into<Modifier>>(&mut self, modifier: M) -> &mut Self {
+        self.modifiers.push(modifier.into());
         self
     }
 
     /// Add a render modifier to the effect.
-    pub fn render<M: RenderModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
-        modifier.apply(&mut self.render_layout);
-        //self.modifiers.push(Box::new(modifier));
+    pub fn render<M: RenderModifier + Into<Modifier>>(mut self, modifier: M) -> Self {
+        self.modifiers.push(modifier.into());
+        self
+    }
+
+    /// Add an initialization modifier defined outside of this crate to the effect.
+    ///
+    /// The modifier is stored as a [`CustomModifier`], which can't be serialized.
+    pub fn init_custom<M: InitModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
+        let modifier = CustomModifier::Init(Arc::new(modifier));
+        self.modifiers.push(Modifier::Custom(modifier));
         self
     }
+
+    /// Add an update modifier defined outside of this crate to the effect.
+    ///
+    /// The modifier is stored as a [`CustomModifier`], which can't be serialized.
+    pub fn update_custom<M: UpdateModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
+        let modifier = CustomModifier::Update(Arc::new(modifier));
+        self.modifiers.push(Modifier::Custom(modifier));
+        self
+    }
+
+    /// Add a render modifier defined outside of this crate to the effect.
+    ///
+    /// The modifier is stored as a [`CustomModifier`], which can't be serialized.
+    pub fn render_custom<M: RenderModifier + Send + Sync + 'static>(mut self, modifier: M) -> Self {
+        let modifier = CustomModifier::Render(Arc::new(modifier));
+        self.modifiers.push(Modifier::Custom(modifier));
+        self
+    }
+
+    /// Check the effect for invalid values.
+    ///
+    /// All the problems found are collected, instead of stopping at the first one, so that a
+    /// single pass is enough to fix an effect file. The [`EffectAssetLoader`] calls this to
+    /// reject invalid `.effect` files.
+    pub fn validate(&self) -> Result<(), EffectValidationError> {
+        self.validate_as(None)
+    }
+
+    /// Check the effect for invalid values, knowing the asset path it's loaded from.
+    ///
+    /// In addition to the checks of [`validate()`], this rejects a sub-emitter referencing the
+    /// effect itself by path, which would otherwise spawn a new nested instance every frame.
+    ///
+    /// [`validate()`]: EffectAsset::validate
+    pub fn validate_as(&self, path: Option<&Path>) -> Result<(), EffectValidationError> {
+        let mut errors = vec![];
+
+        if self.capacity == 0 {
+            errors.push(EffectAssetError::ZeroCapacity);
+        } else if self.capacity.checked_mul(particle_item_size()).is_none() {
+            errors.push(EffectAssetError::CapacityOverflow {
+                capacity: self.capacity,
+                item_size: particle_item_size(),
+            });
+        }
+
+        let period = self.spawner.period();
+        let [min, max] = period.range();
+        if !(min >= 0. && max > 0.) {
+            errors.push(EffectAssetError::InvalidSpawnerPeriod { period });
+        }
+
+        for (index, modifier) in self.modifiers.iter().enumerate() {
+            match modifier {
+                Modifier::ColorOverLifetime(modifier) => {
+                    let gradient = match &modifier.gradient {
+                        GradientEnum::Gradient(gradient) => gradient,
+                        GradientEnum::ColorSelector(selector) => &selector.gradient,
+                    };
+                    validate_gradient(index, gradient, &mut errors);
+                }
+                Modifier::SizeOverLifetime(modifier) => {
+                    validate_gradient(index, &modifier.gradient, &mut errors);
+                }
+                Modifier::RotationOverLifetime(modifier) => {
+                    validate_gradient(index, &modifier.gradient, &mut errors);
+                }
+                Modifier::Flipbook(modifier) => {
+                    if modifier.columns == 0 || modifier.rows == 0 {
+                        errors.push(EffectAssetError::InvalidFlipbookSize {
+                            modifier_index: index,
+                            columns: modifier.columns,
+                            rows: modifier.rows,
+                        });
+                    }
+                    if let FlipbookMode::Fps { fps } = modifier.mode {
+                        if !(fps > 0.) {
+                            errors.push(EffectAssetError::InvalidFlipbookFps {
+                                modifier_index: index,
+                                fps,
+                            });
+                        }
+                    }
+                }
+                Modifier::PositionCone(modifier) => {
+                    let angle = modifier.angle;
+                    if !(0. ..std::f32::consts::FRAC_PI_2).contains(&angle) {
+                        errors.push(EffectAssetError::InvalidConeAngle {
+                            modifier_index: index,
+                            angle,
+                        });
+                    }
+                }
+                Modifier::Lifetime(modifier) => {
+                    let [min, _] = modifier.lifetime.range();
+                    if !(min > 0.) {
+                        errors.push(EffectAssetError::InvalidLifetime {
+                            modifier_index: index,
+                            lifetime: modifier.lifetime,
+                        });
+                    }
+                }
+                Modifier::PositionCurve(modifier) => {
+                    if !modifier.has_valid_points() {
+                        errors.push(EffectAssetError::InvalidCurvePoints {
+                            modifier_index: index,
+                            count: modifier.points.len(),
+                        });
+                    }
+                }
+                Modifier::SubEmitter(modifier) => {
+                    if modifier.particles_per_event == 0 {
+                        errors.push(EffectAssetError::ZeroParticlesPerEvent {
+                            modifier_index: index,
+                        });
+                    }
+                    let effect_path = modifier.effect_path.as_deref().map(Path::new);
+                    if path.is_some() && effect_path == path {
+                        errors.push(EffectAssetError::SelfSubEmitter {
+                            modifier_index: index,
+                        });
+                    }
+                }
+                Modifier::Drag(modifier) => {
+                    if !(modifier.linear >= 0. && modifier.quadratic >= 0.) {
+                        errors.push(EffectAssetError::InvalidDrag {
+                            modifier_index: index,
+                            linear: modifier.linear,
+                            quadratic: modifier.quadratic,
+                        });
+                    }
+                }
+                Modifier::CurlNoise(modifier) => {
+                    if !(1..=CurlNoiseModifier::MAX_OCTAVES).contains(&modifier.octaves) {
+                        errors.push(EffectAssetError::InvalidNoiseOctaves {
+                            modifier_index: index,
+                            octaves: modifier.octaves,
+                        });
+                    }
+                }
+                Modifier::Vortex(modifier) => {
+                    if !(modifier.min_radius >= 0. && modifier.max_radius > modifier.min_radius) {
+                        errors.push(EffectAssetError::InvalidVortexRadius {
+                            modifier_index: index,
+                            min_radius: modifier.min_radius,
+                            max_radius: modifier.max_radius,
+                        });
+                    }
+                    if !(modifier.stiffness >= 0. && modifier.stiffness.is_finite()) {
+                        errors.push(EffectAssetError::InvalidVortexStiffness {
+                            modifier_index: index,
+                            stiffness: modifier.stiffness,
+                        });
+                    }
+                }
+                Modifier::Collision(modifier) => {
+                    if let CollisionResponse::Bounce {
+                        restitution,
+                        friction,
+                    } = modifier.response
+                    {
+                        if !((0. ..=1.).contains(&restitution) && (0. ..=1.).contains(&friction)) {
+                            errors.push(EffectAssetError::InvalidCollisionResponse {
+                                modifier_index: index,
+                                restitution,
+                                friction,
+                            });
+                        }
+                    }
+                }
+                Modifier::SpeedLimit(modifier) => {
+                    if !(modifier.min_speed >= 0. && modifier.max_speed >= modifier.min_speed) {
+                        errors.push(EffectAssetError::InvalidSpeedLimit {
+                            modifier_index: index,
+                            min_speed: modifier.min_speed,
+                            max_speed: modifier.max_speed,
+                        });
+                    }
+                }
+                Modifier::ForceField(modifier) => {
+                    for (source_index, source) in modifier.force_field.iter().enumerate() {
+                        if !(source.min_radius >= 0. && source.max_radius.is_finite()) {
+                            errors.push(EffectAssetError::InvalidForceFieldRadius {
+                                modifier_index: index,
+                                source_index,
+                                min_radius: source.min_radius,
+                                max_radius: source.max_radius,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Force field modifiers stack their sources, which must all fit in a single field
+        let force_field_sources = self
+            .modifiers
+            .iter()
+            .filter_map(|modifier| match modifier {
+                Modifier::ForceField(modifier) => Some(modifier.source_count()),
+                _ => None,
+            })
+            .sum::<usize>();
+        if force_field_sources > FFNUM {
+            errors.push(EffectAssetError::TooManyForceFieldSources {
+                count: force_field_sources,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(EffectValidationError {
+                name: self.name.clone(),
+                errors,
+            })
+        }
+    }
+
+    /// Check the effect for invalid values, and that its particle buffer fits in a single storage
+    /// buffer binding of at most `max_binding_size` bytes.
+    ///
+    /// The render world calls this with the limit of the render device before allocating the
+    /// particles of an effect, and skips the effect if invalid. Effects built in code bypass the
+    /// [`EffectAssetLoader`], so this is their only check.
+    pub fn validate_for_device(&self, max_binding_size: u32) -> Result<(), EffectValidationError> {
+        let mut errors = match self.validate() {
+            Ok(()) => vec![],
+            Err(err) => err.errors,
+        };
+        let byte_size = self.capacity as u64 * particle_item_size() as u64;
+        if byte_size > max_binding_size as u64 {
+            errors.push(EffectAssetError::CapacityExceedsBindingSize {
+                capacity: self.capacity,
+                byte_size,
+                max_binding_size,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(EffectValidationError {
+                name: self.name.clone(),
+                errors,
+            })
+        }
+    }
+
+    /// Build the init layout of the effect by applying all its init modifiers in order.
+    pub fn init_layout(&self) -> InitLayout {
+        let mut init_layout = InitLayout::default();
+        for modifier in self.modifiers.iter().filter_map(Modifier::as_init) {
+            modifier.apply(&mut init_layout);
+        }
+        init_layout
+    }
+
+    /// Build the update layout of the effect by applying all its update modifiers in order.
+    pub fn update_layout(&self) -> UpdateLayout {
+        let mut update_layout = UpdateLayout::default();
+        for modifier in self.modifiers.iter().filter_map(Modifier::as_update) {
+            modifier.apply(&mut update_layout);
+        }
+        update_layout
+    }
+
+    /// Build the render layout of the effect by applying all its render modifiers in order.
+    pub fn render_layout(&self) -> RenderLayout {
+        let mut render_layout = RenderLayout::default();
+        for modifier in self.modifiers.iter().filter_map(Modifier::as_render) {
+            modifier.apply(&mut render_layout);
+        }
+        render_layout
+    }
+}
+
+/// Check the keys of a gradient used by the modifier at index `modifier_index`.
+fn validate_gradient<T: Lerp>(
+    modifier_index: usize,
+    gradient: &Gradient<T>,
+    errors: &mut Vec<EffectAssetError>,
+) {
+    let keys = gradient.keys();
+    for key in keys {
+        let ratio = key.ratio();
+        if !(0. ..=1.).contains(&ratio) {
+            errors.push(EffectAssetError::InvalidGradientRatio {
+                modifier_index,
+                ratio,
+            });
+        }
+    }
+    if keys.windows(2).any(|w| w[0].ratio() > w[1].ratio()) {
+        errors.push(EffectAssetError::UnsortedGradientKeys { modifier_index });
+    }
 }
 
+/// An effect deriving from a base effect, overriding only some of its fields.
+///
+/// This is the content of an `.effect` file declaring a `base` effect by path, instead of a
+/// complete [`EffectAsset`]. The fields present in the file are merged into those of the base
+/// effect, recursively, while the absent ones are inherited. So a struct like the spawner only
+/// lists the fields to change, whereas a sequence like the keys of a gradient is replaced as a
+/// whole. The base effect can itself derive from another one.
+///
+/// Modifiers of the base effect are overridden by the name of their [`Modifier`] variant,
+/// which targets the first modifier of that kind, optionally followed by `#` and the index of
+/// the modifier among those of the same kind, like `"PositionSphere#1"` for the second sphere
+/// emitter. The modifiers listed in `extra_modifiers` are appended after those of the base
+/// effect.
+///
+/// ```ron
+/// (
+///     base: "smoke.effect",
+///     name: "green_smoke",
+///     capacity: 2048,
+///     spawner: (num_particles: Single(60.0)),
+///     modifiers: {
+///         "ColorOverLifetime": (gradient: Gradient((keys: [
+///             (ratio: 0.0, value: (0.0, 1.0, 0.0, 1.0)),
+///             (ratio: 1.0, value: (0.0, 1.0, 0.0, 0.0)),
+///         ]))),
+///     },
+/// )
+/// ```
+#[derive(Default, Clone)]
+pub struct EffectTemplate {
+    /// Path of the base `.effect` file, relative to the asset folder.
+    pub base: String,
+    /// Fields of the file overriding those of the base effect.
+    overrides: Vec<(String, RonNode)>,
+}
+
+impl EffectTemplate {
+    /// Maximum number of chained base effects, to catch cycles between `.effect` files.
+    pub const MAX_DEPTH: usize = 16;
+
+    /// Parse a template from the content of an `.effect` file.
+    ///
+    /// Unlike a plain [`EffectAsset`], the optional fields can be written without `Some(...)`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ron::Error> {
+        let overrides = match RonNode::parse(std::str::from_utf8(bytes)?)? {
+            RonNode::Struct { fields, .. } => fields,
+            _ => {
+                return Err(ron::Error {
+                    code: ron::ErrorCode::ExpectedStruct,
+                    position: ron::error::Position { line: 1, col: 1 },
+                })
+            }
+        };
+        let base = match overrides.iter().find(|(name, _)| name == "base") {
+            Some((_, base)) => ron::from_str(&base.to_string())?,
+            None => String::new(),
+        };
+        Ok(EffectTemplate { base, overrides })
+    }
+
+    /// Get the path of the base effect declared in the content of an `.effect` file, if any.
+    ///
+    /// Returns `None` for a file describing a complete [`EffectAsset`].
+    pub fn base_path(bytes: &[u8]) -> Option<String> {
+        #[derive(Deserialize)]
+        struct Base {
+            #[serde(default)]
+            base: String,
+        }
+        ron::de::from_bytes::<Base>(bytes)
+            .ok()
+            .map(|b| b.base)
+            .filter(|base| !base.is_empty())
+    }
+
+    /// Apply the overrides of this template to the given base effect.
+    pub fn apply(&self, effect: EffectAsset) -> Result<EffectAsset, EffectAssetError> {
+        let invalid = |message: String| EffectAssetError::InvalidTemplate { message };
+        let base = ron::to_string(&effect).map_err(|err| invalid(err.to_string()))?;
+        let mut node = RonNode::parse(&base).map_err(|err| invalid(err.to_string()))?;
+        for (name, value) in &self.overrides {
+            match (name.as_str(), value) {
+                ("base", _) => {}
+                ("modifiers", RonNode::Map(entries)) => {
+                    for (key, value) in entries {
+                        let key: String = ron::from_str(&key.to_string())
+                            .map_err(|err| invalid(err.to_string()))?;
+                        let modifier = find_modifier(&mut node, &key)
+                            .ok_or(EffectAssetError::InvalidModifierOverride { key })?;
+                        modifier.merge(value.clone());
+                    }
+                }
+                ("extra_modifiers", RonNode::Seq(extra_modifiers)) => {
+                    if let Some(RonNode::Seq(modifiers)) = node.field_mut("modifiers") {
+                        modifiers.extend(extra_modifiers.iter().cloned());
+                    }
+                }
+                ("modifiers", _) | ("extra_modifiers", _) => {
+                    return Err(invalid(format!("unexpected value for field `{}`", name)));
+                }
+                _ => match node.field_mut(name) {
+                    Some(field) => field.merge(value.clone()),
+                    None => return Err(invalid(format!("unknown field `{}`", name))),
+                },
+            }
+        }
+        ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(&node.to_string())
+            .map_err(|err| invalid(err.to_string()))
+    }
+}
+
+/// Find the content of a modifier in the RON tree of an effect, from the name of its
+/// [`Modifier`] variant optionally followed by `#` and its index among the modifiers of that
+/// kind.
+fn find_modifier<'a>(effect: &'a mut RonNode, key: &str) -> Option<&'a mut RonNode> {
+    let (variant, index) = match key.split_once('#') {
+        Some((variant, index)) => (variant, index.parse().ok()?),
+        None => (key, 0),
+    };
+    match effect.field_mut("modifiers")? {
+        RonNode::Seq(modifiers) => modifiers
+            .iter_mut()
+            .filter_map(|modifier| match modifier {
+                RonNode::Tuple {
+                    ident: Some(ident),
+                    items,
+                } if ident == variant && items.len() == 1 => Some(&mut items[0]),
+                _ => None,
+            })
+            .nth(index),
+        _ => None,
+    }
+}
+
+/// A single problem found by [`EffectAsset::validate()`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum EffectAssetError {
+    /// The effect capacity is zero, so the effect cannot hold any particle.
+    ZeroCapacity,
+    /// The size in bytes of the particle buffer of the effect doesn't fit in 32 bits.
+    CapacityOverflow {
+        /// Capacity of the effect, in number of particles.
+        capacity: u32,
+        /// Size of a single particle, in bytes.
+        item_size: u32,
+    },
+    /// The particle buffer of the effect is larger than the render device can bind at once.
+    CapacityExceedsBindingSize {
+        /// Capacity of the effect, in number of particles.
+        capacity: u32,
+        /// Size of the particle buffer, in bytes.
+        byte_size: u64,
+        /// Maximum size of a storage buffer binding of the render device, in bytes.
+        max_binding_size: u32,
+    },
+    /// The spawner period can be negative, or can only be zero.
+    InvalidSpawnerPeriod {
+        /// Period of the spawner.
+        period: Value<f32>,
+    },
+    /// A gradient key has a ratio outside of the \[0:1\] range.
+    InvalidGradientRatio {
+        /// Index of the modifier owning the gradient, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Ratio of the invalid key.
+        ratio: f32,
+    },
+    /// The keys of a gradient are not sorted by increasing ratio.
+    UnsortedGradientKeys {
+        /// Index of the modifier owning the gradient, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+    },
+    /// An [`EffectTemplate`] overrides a modifier which doesn't exist in its base effect.
+    InvalidModifierOverride {
+        /// Key of the overridden modifier, made of the name of its [`Modifier`] variant and
+        /// optionally of its index among the modifiers of that kind.
+        key: String,
+    },
+    /// The overrides of an [`EffectTemplate`] don't make a valid effect once merged into its
+    /// base effect.
+    InvalidTemplate {
+        /// Description of the problem.
+        message: String,
+    },
+    /// A cone emitter has a half-angle outside of the \[0:π/2\[ range.
+    InvalidConeAngle {
+        /// Index of the cone modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Half-angle of the cone, in radians.
+        angle: f32,
+    },
+    /// A particle lifetime can be zero or negative.
+    InvalidLifetime {
+        /// Index of the lifetime modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Lifetime of the particles.
+        lifetime: Value<f32>,
+    },
+    /// A curve emitter has a number of points not matching its kind of curve.
+    InvalidCurvePoints {
+        /// Index of the curve modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Number of points of the curve.
+        count: usize,
+    },
+    /// A sub-emitter spawns zero particles per death event.
+    ZeroParticlesPerEvent {
+        /// Index of the sub-emitter modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+    },
+    /// A sub-emitter references the effect it belongs to.
+    SelfSubEmitter {
+        /// Index of the sub-emitter modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+    },
+    /// The chain of sub-emitters of the effect, followed through their `.effect` files, comes
+    /// back to an effect already in the chain.
+    SubEmitterCycle {
+        /// Index of the sub-emitter modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Asset paths of the effects of the chain, starting from the effect itself and ending
+        /// with the effect found twice.
+        chain: Vec<String>,
+    },
+    /// A force field source has a negative minimum radius or a non-finite maximum radius.
+    InvalidForceFieldRadius {
+        /// Index of the force field modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Index of the source in the force field.
+        source_index: usize,
+        /// Minimum radius of the source.
+        min_radius: f32,
+        /// Maximum radius of the source.
+        max_radius: f32,
+    },
+    /// A drag coefficient is negative.
+    InvalidDrag {
+        /// Index of the drag modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Linear drag coefficient.
+        linear: f32,
+        /// Quadratic drag coefficient.
+        quadratic: f32,
+    },
+    /// A curl noise has a number of octaves outside of the
+    /// \[1:[`CurlNoiseModifier::MAX_OCTAVES`]\] range.
+    InvalidNoiseOctaves {
+        /// Index of the curl noise modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Number of noise octaves.
+        octaves: u32,
+    },
+    /// A vortex has a negative minimum radius, or a maximum radius not greater than its
+    /// minimum radius.
+    InvalidVortexRadius {
+        /// Index of the vortex modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Minimum radius of the vortex.
+        min_radius: f32,
+        /// Maximum radius of the vortex.
+        max_radius: f32,
+    },
+    /// A vortex has a negative or infinite stiffness.
+    InvalidVortexStiffness {
+        /// Index of the vortex modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Stiffness of the vortex.
+        stiffness: f32,
+    },
+    /// A collision bounce has a restitution or a friction outside of the \[0:1\] range.
+    InvalidCollisionResponse {
+        /// Index of the collision modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Restitution of the bounce.
+        restitution: f32,
+        /// Friction of the bounce.
+        friction: f32,
+    },
+    /// A speed limit has a negative minimum speed, or a maximum speed smaller than its minimum
+    /// speed.
+    InvalidSpeedLimit {
+        /// Index of the speed limit modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Minimum speed of the particles.
+        min_speed: f32,
+        /// Maximum speed of the particles.
+        max_speed: f32,
+    },
+    /// A flipbook has zero columns or rows.
+    InvalidFlipbookSize {
+        /// Index of the flipbook modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Number of columns of the sprite sheet.
+        columns: u32,
+        /// Number of rows of the sprite sheet.
+        rows: u32,
+    },
+    /// A flipbook plays at a zero or negative frame rate.
+    InvalidFlipbookFps {
+        /// Index of the flipbook modifier, in [`EffectAsset::modifiers`].
+        modifier_index: usize,
+        /// Frame rate of the flipbook.
+        fps: f32,
+    },
+    /// The force field modifiers of the effect have more sources in total than [`FFNUM`].
+    TooManyForceFieldSources {
+        /// Total number of sources of all force field modifiers.
+        count: usize,
+    },
+}
+
+impl fmt::Display for EffectAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroCapacity => write!(f, "capacity must be greater than zero"),
+            Self::CapacityOverflow {
+                capacity,
+                item_size,
+            } => write!(
+                f,
+                "capacity {} overflows the particle buffer size ({} bytes per particle)",
+                capacity, item_size
+            ),
+            Self::CapacityExceedsBindingSize {
+                capacity,
+                byte_size,
+                max_binding_size,
+            } => write!(
+                f,
+                "capacity {} needs a particle buffer of {} bytes, more than the maximum of {} bytes \
+                of the render device",
+                capacity, byte_size, max_binding_size
+            ),
+            Self::InvalidSpawnerPeriod { period } => write!(
+                f,
+                "spawner period {:?} must be able to generate a positive number and no negative numbers",
+                period
+            ),
+            Self::InvalidGradientRatio {
+                modifier_index,
+                ratio,
+            } => write!(
+                f,
+                "modifier #{}: gradient key ratio {} is outside of the [0:1] range",
+                modifier_index, ratio
+            ),
+            Self::UnsortedGradientKeys { modifier_index } => write!(
+                f,
+                "modifier #{}: gradient keys are not sorted by increasing ratio",
+                modifier_index
+            ),
+            Self::InvalidModifierOverride { key } => write!(
+                f,
+                "cannot override modifier '{}', the base effect has no such modifier",
+                key
+            ),
+            Self::InvalidTemplate { message } => {
+                write!(f, "cannot apply the overrides of the effect: {}", message)
+            }
+            Self::InvalidConeAngle {
+                modifier_index,
+                angle,
+            } => write!(
+                f,
+                "modifier #{}: cone half-angle {} is outside of the [0:π/2[ range",
+                modifier_index, angle
+            ),
+            Self::InvalidLifetime {
+                modifier_index,
+                lifetime,
+            } => write!(
+                f,
+                "modifier #{}: particle lifetime {:?} must be strictly positive",
+                modifier_index, lifetime
+            ),
+            Self::InvalidCurvePoints {
+                modifier_index,
+                count,
+            } => write!(
+                f,
+                "modifier #{}: {} points is not a valid number of points for this kind of curve",
+                modifier_index, count
+            ),
+            Self::ZeroParticlesPerEvent { modifier_index } => write!(
+                f,
+                "modifier #{}: sub-emitter must spawn at least one particle per event",
+                modifier_index
+            ),
+            Self::SelfSubEmitter { modifier_index } => write!(
+                f,
+                "modifier #{}: sub-emitter cannot use its own effect as child effect",
+                modifier_index
+            ),
+            Self::SubEmitterCycle {
+                modifier_index,
+                chain,
+            } => write!(
+                f,
+                "modifier #{}: sub-emitters form a cycle ({})",
+                modifier_index,
+                chain.join(" -> ")
+            ),
+            Self::InvalidForceFieldRadius {
+                modifier_index,
+                source_index,
+                min_radius,
+                max_radius,
+            } => write!(
+                f,
+                "modifier #{}: force field source #{} has invalid radii (min_radius={}, max_radius={})",
+                modifier_index, source_index, min_radius, max_radius
+            ),
+            Self::InvalidDrag {
+                modifier_index,
+                linear,
+                quadratic,
+            } => write!(
+                f,
+                "modifier #{}: drag coefficients must be positive (linear={}, quadratic={})",
+                modifier_index, linear, quadratic
+            ),
+            Self::InvalidNoiseOctaves {
+                modifier_index,
+                octaves,
+            } => write!(
+                f,
+                "modifier #{}: curl noise has {} octaves, must be between 1 and {}",
+                modifier_index,
+                octaves,
+                CurlNoiseModifier::MAX_OCTAVES
+            ),
+            Self::InvalidVortexRadius {
+                modifier_index,
+                min_radius,
+                max_radius,
+            } => write!(
+                f,
+                "modifier #{}: vortex has invalid radii (min_radius={}, max_radius={})",
+                modifier_index, min_radius, max_radius
+            ),
+            Self::InvalidVortexStiffness {
+                modifier_index,
+                stiffness,
+            } => write!(
+                f,
+                "modifier #{}: vortex stiffness {} must be positive and finite",
+                modifier_index, stiffness
+            ),
+            Self::InvalidCollisionResponse {
+                modifier_index,
+                restitution,
+                friction,
+            } => write!(
+                f,
+                "modifier #{}: collision restitution {} and friction {} must be in the [0:1] range",
+                modifier_index, restitution, friction
+            ),
+            Self::InvalidSpeedLimit {
+                modifier_index,
+                min_speed,
+                max_speed,
+            } => write!(
+                f,
+                "modifier #{}: invalid speed limit (min_speed={}, max_speed={})",
+                modifier_index, min_speed, max_speed
+            ),
+            Self::InvalidFlipbookSize {
+                modifier_index,
+                columns,
+                rows,
+            } => write!(
+                f,
+                "modifier #{}: flipbook of {}x{} frames must have at least one column and one row",
+                modifier_index, columns, rows
+            ),
+            Self::InvalidFlipbookFps {
+                modifier_index,
+                fps,
+            } => write!(
+                f,
+                "modifier #{}: flipbook frame rate {} must be strictly positive",
+                modifier_index, fps
+            ),
+            Self::TooManyForceFieldSources { count } => write!(
+                f,
+                "force field modifiers have {} sources in total, more than the maximum of {}",
+                count, FFNUM
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EffectAssetError {}
+
+/// Error returned by [`EffectAsset::validate()`], listing all the problems found in the effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectValidationError {
+    /// Name of the invalid effect.
+    pub name: String,
+    /// All the problems found in the effect.
+    pub errors: Vec<EffectAssetError>,
+}
+
+impl fmt::Display for EffectValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid effect '{}' ({} error(s)):",
+            self.name,
+            self.errors.len()
+        )?;
+        for error in &self.errors {
+            write!(f, "\n- {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EffectValidationError {}
+
 #[derive(Default)]
 pub struct EffectAssetLoader;
 
@@ -124,8 +1048,41 @@ impl AssetLoader for EffectAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let custom_asset = ron::de::from_bytes::<EffectAsset>(bytes)?;
-            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            let mut dependencies = vec![];
+            let mut custom_asset = load_effect(bytes, load_context, &mut dependencies).await?;
+            custom_asset.validate_as(Some(load_context.path()))?;
+            check_sub_emitter_cycle(&custom_asset, load_context).await?;
+            // Resolve the texture, sub-emitter, and mesh paths into actual asset handles, and make
+            // them dependencies of the effect so they get loaded alongside it.
+            for modifier in custom_asset.modifiers.iter_mut() {
+                match modifier {
+                    Modifier::ParticleTexture(modifier) => {
+                        if let Some(path) = &modifier.texture_path {
+                            let path = AssetPath::from(path.as_str()).to_owned();
+                            modifier.texture = load_context.get_handle(path.clone());
+                            dependencies.push(path);
+                        }
+                    }
+                    Modifier::SubEmitter(modifier) => {
+                        if let Some(path) = &modifier.effect_path {
+                            let path = AssetPath::from(path.as_str()).to_owned();
+                            modifier.effect = load_context.get_handle(path.clone());
+                            dependencies.push(path);
+                        }
+                    }
+                    Modifier::MeshSurface(modifier) => {
+                        if let Some(path) = &modifier.mesh_path {
+                            let path = AssetPath::from(path.as_str()).to_owned();
+                            modifier.mesh = load_context.get_handle(path.clone());
+                            dependencies.push(path);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            load_context.set_default_asset(
+                LoadedAsset::new(custom_asset).with_dependencies(dependencies),
+            );
             Ok(())
         })
     }
@@ -134,3 +1091,544 @@ impl AssetLoader for EffectAssetLoader {
         &["effect"]
     }
 }
+
+/// Build the effect described by the content of an `.effect` file.
+///
+/// Follows the chain of base effects until a complete effect is found, then applies the
+/// overrides of all the templates found on the way, starting from the deepest. The paths of
+/// the base effects are added to `dependencies`.
+async fn load_effect(
+    bytes: &[u8],
+    load_context: &LoadContext<'_>,
+    dependencies: &mut Vec<AssetPath<'static>>,
+) -> Result<EffectAsset, anyhow::Error> {
+    let mut templates = vec![];
+    let mut base_bytes: Option<Vec<u8>> = None;
+    let mut effect = loop {
+        let bytes = base_bytes.as_deref().unwrap_or(bytes);
+        let base = match EffectTemplate::base_path(bytes) {
+            Some(base) => base,
+            None => break ron::de::from_bytes::<EffectAsset>(bytes)?,
+        };
+        if templates.len() >= EffectTemplate::MAX_DEPTH {
+            anyhow::bail!(
+                "Too many nested base effects loading {:?}, is there a cycle?",
+                load_context.path()
+            );
+        }
+        templates.push(EffectTemplate::from_bytes(bytes)?);
+        let path = AssetPath::from(base.as_str()).to_owned();
+        base_bytes = Some(load_context.read_asset_bytes(path.path()).await?);
+        dependencies.push(path);
+    };
+    for template in templates.iter().rev() {
+        effect = template.apply(effect)?;
+    }
+    Ok(effect)
+}
+
+/// Get the index and child effect path of the sub-emitter modifier in effect, if any.
+fn sub_emitter_path(effect: &EffectAsset) -> Option<(usize, &str)> {
+    // Like the update layout, only consider the last sub-emitter
+    for (index, modifier) in effect.modifiers.iter().enumerate().rev() {
+        if let Modifier::SubEmitter(modifier) = modifier {
+            return modifier.effect_path.as_deref().map(|path| (index, path));
+        }
+    }
+    None
+}
+
+/// Reject an effect whose chain of sub-emitters comes back to an effect already in the chain.
+///
+/// The `.effect` files of the sub-emitters are read and followed the same way the chain of
+/// base effects is, up to [`EffectTemplate::MAX_DEPTH`] files. A sub-emitter file which can't
+/// be read or parsed ends the chain; its own loading reports the problem.
+async fn check_sub_emitter_cycle(
+    effect: &EffectAsset,
+    load_context: &LoadContext<'_>,
+) -> Result<(), EffectValidationError> {
+    let (modifier_index, path) = match sub_emitter_path(effect) {
+        Some(sub_emitter) => sub_emitter,
+        None => return Ok(()),
+    };
+    let mut chain = vec![load_context.path().to_path_buf(), PathBuf::from(path)];
+    while chain.len() <= EffectTemplate::MAX_DEPTH {
+        let path = chain.last().unwrap();
+        if chain[..chain.len() - 1].contains(path) {
+            return Err(EffectValidationError {
+                name: effect.name.clone(),
+                errors: vec![EffectAssetError::SubEmitterCycle {
+                    modifier_index,
+                    chain: chain
+                        .iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .collect(),
+                }],
+            });
+        }
+        let bytes = match load_context.read_asset_bytes(path).await {
+            Ok(bytes) => bytes,
+            Err(_) => break,
+        };
+        let child = match load_effect(&bytes, load_context, &mut vec![]).await {
+            Ok(child) => child,
+            Err(_) => break,
+        };
+        match sub_emitter_path(&child) {
+            Some((_, path)) => chain.push(PathBuf::from(path)),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AccelModifier, ColorOverLifetimeModifier, ColorTintModifier, CurveKind, ForceFieldModifier,
+        LifetimeModifier, ParticleTextureModifier, PositionCircleModifier, PositionConeModifier,
+        PositionCurveModifier, PositionSphereModifier, ShapeDimension, SizeOverLifetimeModifier,
+        SubEmitterModifier,
+    };
+    use bevy::asset::HandleId;
+
+    #[test]
+    fn template() {
+        let base = EffectAsset {
+            name: "Smoke".to_string(),
+            capacity: 1024,
+            spawner: Spawner::rate(30.0.into()),
+            ..Default::default()
+        }
+        .update(AccelModifier {
+            accel: Vec3::new(0., 1., 0.),
+        });
+        let base_ron = ron::ser::to_string(&base).unwrap();
+        assert!(EffectTemplate::base_path(base_ron.as_bytes()).is_none());
+
+        let base = base
+            .init(PositionSphereModifier {
+                center: Vec3::ZERO,
+                radius: 1.,
+                dimension: ShapeDimension::Surface,
+            })
+            .init(PositionSphereModifier {
+                center: Vec3::new(0., 2., 0.),
+                radius: 1.,
+                dimension: ShapeDimension::Surface,
+            })
+            .render(ColorOverLifetimeModifier::default());
+
+        // Only the fields present in the template change, and modifiers are selected by kind
+        let s = r#"(
+            base: "smoke.effect",
+            capacity: 2048,
+            spawner: (num_particles: Single(60.0)),
+            modifiers: {
+                "Accel": (accel: (0.0, 2.0, 0.0)),
+                "PositionSphere#1": (radius: 3.0),
+                "ColorOverLifetime": (gradient: Gradient((keys: [
+                    (ratio: 0.0, value: (0.0, 1.0, 0.0, 1.0)),
+                ]))),
+            },
+            extra_modifiers: [ColorTint((color: (1.0, 0.5, 0.5, 1.0)))],
+        )"#;
+        assert_eq!(
+            EffectTemplate::base_path(s.as_bytes()).as_deref(),
+            Some("smoke.effect")
+        );
+        let template = EffectTemplate::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(template.base, "smoke.effect");
+        let effect = template.apply(base.clone()).unwrap();
+        assert_eq!(effect.name, "Smoke");
+        assert_eq!(effect.capacity, 2048);
+        assert_eq!(effect.spawner, Spawner::rate(60.0.into()));
+        assert_eq!(effect.update_layout().accel, Vec3::new(0., 2., 0.));
+        assert_eq!(effect.modifiers.len(), base.modifiers.len() + 1);
+        match (&effect.modifiers[1], &effect.modifiers[2]) {
+            (Modifier::PositionSphere(first), Modifier::PositionSphere(second)) => {
+                assert_eq!(first.radius, 1.);
+                assert_eq!(second.center, Vec3::new(0., 2., 0.));
+                assert_eq!(second.radius, 3.);
+            }
+            _ => panic!("expected two sphere modifiers"),
+        }
+        let render_layout = effect.render_layout();
+        match &render_layout.lifetime_color_gradients[0] {
+            GradientEnum::Gradient(gradient) => {
+                assert_eq!(*gradient, Gradient::constant(Vec4::new(0., 1., 0., 1.)));
+            }
+            _ => panic!("expected a plain gradient"),
+        }
+        assert_eq!(render_layout.color_tint, Some(Vec4::new(1., 0.5, 0.5, 1.)));
+
+        let template = EffectTemplate::from_bytes(
+            br#"(base: "smoke.effect", modifiers: { "Accel#1": (accel: (0.0, 2.0, 0.0)) })"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.apply(base.clone()).err(),
+            Some(EffectAssetError::InvalidModifierOverride {
+                key: "Accel#1".to_string()
+            })
+        );
+
+        let template =
+            EffectTemplate::from_bytes(br#"(base: "smoke.effect", capcity: 2048)"#).unwrap();
+        assert!(matches!(
+            template.apply(base),
+            Err(EffectAssetError::InvalidTemplate { .. })
+        ));
+    }
+
+    #[test]
+    fn validate() {
+        let effect = EffectAsset {
+            name: "Effect".to_string(),
+            capacity: 1024,
+            spawner: Spawner::rate(30.0.into()),
+            ..Default::default()
+        }
+        .render(SizeOverLifetimeModifier {
+            gradient: Gradient::constant(Vec2::ONE),
+        });
+        assert!(effect.validate().is_ok());
+
+        let mut empty = effect.clone();
+        empty.capacity = 0;
+        assert_eq!(
+            empty.validate().unwrap_err().errors,
+            vec![EffectAssetError::ZeroCapacity]
+        );
+
+        // The particle buffer must fit in a storage buffer binding of the render device
+        let byte_size = 1024 * particle_item_size() as u64;
+        assert!(effect.validate_for_device(byte_size as u32).is_ok());
+        assert_eq!(
+            effect
+                .validate_for_device(byte_size as u32 - 1)
+                .unwrap_err()
+                .errors,
+            vec![EffectAssetError::CapacityExceedsBindingSize {
+                capacity: 1024,
+                byte_size,
+                max_binding_size: byte_size as u32 - 1,
+            }]
+        );
+        assert_eq!(
+            empty
+                .validate_for_device(byte_size as u32)
+                .unwrap_err()
+                .errors,
+            vec![EffectAssetError::ZeroCapacity]
+        );
+
+        // Values read from a file bypass the checks of the various constructors
+        let s = ron::ser::to_string(&effect)
+            .unwrap()
+            .replace("period:Single(1.0)", "period:Single(-1.0)")
+            .replace("ratio:0.0", "ratio:2.0");
+        let err = ron::from_str::<EffectAsset>(&s)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.name, "Effect");
+        assert_eq!(
+            err.errors,
+            vec![
+                EffectAssetError::InvalidSpawnerPeriod {
+                    period: Value::Single(-1.)
+                },
+                EffectAssetError::InvalidGradientRatio {
+                    modifier_index: 0,
+                    ratio: 2.
+                },
+            ]
+        );
+
+        let cone = effect
+            .clone()
+            .init(PositionConeModifier {
+                angle: 2.,
+                ..Default::default()
+            })
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            cone.errors,
+            vec![EffectAssetError::InvalidConeAngle {
+                modifier_index: 1,
+                angle: 2.
+            }]
+        );
+
+        let lifetime = LifetimeModifier {
+            lifetime: Value::Uniform((0., 2.)),
+        };
+        assert_eq!(
+            effect.clone().init(lifetime).validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidLifetime {
+                modifier_index: 1,
+                lifetime: Value::Uniform((0., 2.))
+            }]
+        );
+
+        let curve = PositionCurveModifier {
+            kind: CurveKind::Bezier,
+            points: vec![Vec3::ZERO, Vec3::X, Vec3::Y],
+            ..Default::default()
+        };
+        let mut polyline = curve.clone();
+        polyline.kind = CurveKind::Polyline;
+        assert!(effect.clone().init(polyline).validate().is_ok());
+        assert_eq!(
+            effect.clone().init(curve).validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidCurvePoints {
+                modifier_index: 1,
+                count: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn stacking() {
+        let effect = EffectAsset::default()
+            .init(PositionCircleModifier::default())
+            .init(PositionSphereModifier::default())
+            .update(AccelModifier {
+                accel: Vec3::new(0., -9.8, 0.),
+            })
+            .update(AccelModifier {
+                accel: Vec3::new(1., 0., 0.),
+            })
+            .render(ColorOverLifetimeModifier::default())
+            .render(ColorTintModifier {
+                color: Vec4::new(1., 0.5, 0.5, 1.),
+            })
+            .render(ColorTintModifier {
+                color: Vec4::new(0.5, 1., 1., 1.),
+            });
+
+        // Position modifiers are all kept, in order
+        let position_code = effect.init_layout().position_code;
+        let circle = position_code.find("[PositionCircleModifier]").unwrap();
+        let sphere = position_code.find("[PositionSphereModifier]").unwrap();
+        assert!(circle < sphere);
+
+        // Accelerations add up, and tints multiply
+        assert_eq!(effect.update_layout().accel, Vec3::new(1., -9.8, 0.));
+        let render_layout = effect.render_layout();
+        assert_eq!(render_layout.lifetime_color_gradients.len(), 1);
+        assert_eq!(render_layout.color_tint, Some(Vec4::new(0.5, 0.5, 0.5, 1.)));
+
+        // Force field sources are appended after the ones of the previous modifiers
+        let source = |mass| ForceFieldParam {
+            mass,
+            ..Default::default()
+        };
+        let base = EffectAsset {
+            capacity: 1024,
+            ..Default::default()
+        };
+        let effect = base
+            .clone()
+            .update(ForceFieldModifier::new([source(1.), source(2.)]))
+            .update(ForceFieldModifier::new([source(3.)]));
+        assert!(effect.validate().is_ok());
+        let masses: Vec<f32> = effect.update_layout().force_field[..4]
+            .iter()
+            .map(|source| source.mass)
+            .collect();
+        assert_eq!(masses, vec![1., 2., 3., 0.]);
+
+        let effect = (0..FFNUM + 1).fold(base, |effect, _| {
+            effect.update(ForceFieldModifier::new([source(1.)]))
+        });
+        assert_eq!(
+            effect.validate().unwrap_err().errors,
+            vec![EffectAssetError::TooManyForceFieldSources { count: FFNUM + 1 }]
+        );
+    }
+
+    #[test]
+    fn custom_modifier() {
+        struct Wind;
+
+        impl UpdateModifier for Wind {
+            fn apply(&self, update_layout: &mut UpdateLayout) {
+                update_layout.accel += Vec3::X;
+            }
+        }
+
+        let effect = EffectAsset::default()
+            .update(AccelModifier {
+                accel: Vec3::new(0., -9.8, 0.),
+            })
+            .update_custom(Wind);
+        assert!(effect.modifiers[1].as_update().is_some());
+        assert!(effect.modifiers[1].as_init().is_none());
+        assert_eq!(effect.update_layout().accel, Vec3::new(1., -9.8, 0.));
+
+        // Custom modifiers can't be saved to an effect file
+        assert!(ron::ser::to_string(&effect).is_err());
+    }
+
+    #[test]
+    fn sub_emitter() {
+        let child = Handle::weak(HandleId::new(EffectAsset::TYPE_UUID, 42));
+        let effect = EffectAsset {
+            name: "Fireworks".to_string(),
+            capacity: 1024,
+            spawner: Spawner::rate(30.0.into()),
+            ..Default::default()
+        }
+        .update(SubEmitterModifier {
+            effect: child.clone(),
+            effect_path: Some("sparks.effect".to_string()),
+            particles_per_event: 8,
+        });
+        assert!(effect.validate().is_ok());
+        assert_eq!(effect.update_layout().sub_emitter, Some((child, 8)));
+
+        // The handle is not serialized; the loader resolves it back from the path
+        let effect_serde: EffectAsset =
+            ron::from_str(&ron::ser::to_string(&effect).unwrap()).unwrap();
+        match &effect_serde.modifiers[0] {
+            Modifier::SubEmitter(modifier) => {
+                assert_eq!(modifier.effect, Handle::default());
+                assert_eq!(modifier.effect_path.as_deref(), Some("sparks.effect"));
+                assert_eq!(modifier.particles_per_event, 8);
+            }
+            _ => panic!("expected a sub-emitter modifier"),
+        }
+
+        let empty = EffectAsset::default()
+            .update(SubEmitterModifier::default())
+            .validate()
+            .unwrap_err();
+        assert!(empty
+            .errors
+            .contains(&EffectAssetError::ZeroParticlesPerEvent { modifier_index: 0 }));
+
+        // An effect loaded from the same path as its sub-emitter references itself
+        assert!(effect.validate_as(Some(Path::new("fireworks.effect"))).is_ok());
+        assert_eq!(
+            effect
+                .validate_as(Some(Path::new("sparks.effect")))
+                .unwrap_err()
+                .errors,
+            vec![EffectAssetError::SelfSubEmitter { modifier_index: 0 }]
+        );
+    }
+
+    #[test]
+    fn sub_emitter_cycle() {
+        use bevy::{
+            asset::{AssetPlugin, AssetServer, AssetServerSettings, LoadState},
+            prelude::{AddAsset, App, MinimalPlugins},
+        };
+
+        let dir = std::env::temp_dir().join(format!("hanabi_cycle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, sub_emitter: Option<&str>| {
+            let mut effect = EffectAsset {
+                name: name.to_string(),
+                capacity: 1024,
+                spawner: Spawner::rate(30.0.into()),
+                ..Default::default()
+            };
+            if let Some(path) = sub_emitter {
+                effect = effect.update(SubEmitterModifier {
+                    effect: Handle::default(),
+                    effect_path: Some(path.to_string()),
+                    particles_per_event: 1,
+                });
+            }
+            std::fs::write(dir.join(name), ron::ser::to_string(&effect).unwrap()).unwrap();
+        };
+        write("rocket.effect", Some("sparks.effect"));
+        write("sparks.effect", Some("smoke.effect"));
+        write("smoke.effect", None);
+        write("a.effect", Some("b.effect"));
+        write("b.effect", Some("c.effect"));
+        write("c.effect", Some("b.effect"));
+
+        let mut app = App::new();
+        app.insert_resource(AssetServerSettings {
+            asset_folder: dir.to_string_lossy().into_owned(),
+            watch_for_changes: false,
+        })
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_asset::<EffectAsset>()
+        .init_asset_loader::<EffectAssetLoader>();
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        let rocket: Handle<EffectAsset> = asset_server.load("rocket.effect");
+        let a: Handle<EffectAsset> = asset_server.load("a.effect");
+        let states = loop {
+            app.update();
+            let states = [
+                asset_server.get_load_state(&rocket),
+                asset_server.get_load_state(&a),
+            ];
+            if !states.contains(&LoadState::Loading) {
+                break states;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The cycle may start further down the chain
+        assert_eq!(states, [LoadState::Loaded, LoadState::Failed]);
+    }
+
+    #[test]
+    fn serde_ron() {
+        let mut gradient = Gradient::new();
+        gradient.add_key(0.0, Vec4::new(1., 0., 0., 1.));
+        gradient.add_key(1.0, Vec4::splat(0.));
+        let effect = EffectAsset {
+            name: "Effect".to_string(),
+            capacity: 4096,
+            spawner: Spawner::rate(30.0.into()),
+            ..Default::default()
+        }
+        .init(PositionCircleModifier {
+            radius: 2.,
+            speed: 3.0.into(),
+            ..Default::default()
+        })
+        .update(AccelModifier {
+            accel: Vec3::new(0., -3., 0.),
+        })
+        .render(ColorOverLifetimeModifier {
+            gradient: GradientEnum::Gradient(gradient),
+        })
+        .render(ParticleTextureModifier {
+            texture_path: Some("cloud.png".to_string()),
+            ..Default::default()
+        });
+
+        let s = ron::ser::to_string(&effect).unwrap();
+        let effect_serde: EffectAsset = ron::from_str(&s).unwrap();
+        assert_eq!(effect.name, effect_serde.name);
+        assert_eq!(effect.capacity, effect_serde.capacity);
+        assert_eq!(effect.spawner, effect_serde.spawner);
+        assert_eq!(effect.modifiers.len(), effect_serde.modifiers.len());
+        assert_eq!(
+            effect.init_layout().position_code,
+            effect_serde.init_layout().position_code
+        );
+        assert_eq!(
+            effect.update_layout().accel,
+            effect_serde.update_layout().accel
+        );
+        if let Modifier::ParticleTexture(modifier) = &effect_serde.modifiers[3] {
+            assert_eq!(modifier.texture_path.as_deref(), Some("cloud.png"));
+        } else {
+            panic!("Unexpected modifier type.");
+        }
+        assert_eq!(s, ron::ser::to_string(&effect_serde).unwrap());
+    }
+}
@@ -1,9 +1,10 @@
 /// color selector 
 /// 
 use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
 
 /// value range
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct ValueRange {
     /// start
     pub start: f32,
@@ -20,7 +21,7 @@ impl std::hash::Hash for ValueRange {
 }
 
 /// which index shoud apply the rule
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Indicator {
     /// particles' speed
     SPEED,
@@ -30,7 +31,7 @@ pub enum Indicator {
 }
 
 /// color selector
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ColorSelector<T> {
     /// var name in the range
     pub depend_var_name: Indicator,
@@ -2,6 +2,7 @@ use bevy::{
     core::FloatOrd,
     math::{Quat, Vec2, Vec3, Vec3A, Vec4},
 };
+use serde::{Deserialize, Serialize};
 use std::vec::Vec;
 
 use crate::color_selector::{ColorSelector, Indicator};
@@ -55,7 +56,7 @@ impl Lerp for Quat {
 }
 
 /// A single key point for a [`Gradient`].
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct GradientKey<T: Lerp> {
     /// Ratio in \[0:1\] where the key is located.
     ratio: f32,
@@ -79,12 +80,12 @@ impl<T: Lerp> GradientKey<T> {
 /// The gradient can be sampled anywhere, and will return a linear interpolation
 /// of the values of its closest keys. Sampling before 0 or after 1 returns a
 /// constant value equal to the one of the closest bound.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Gradient<T: Lerp> {
     keys: Vec<GradientKey<T>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GradientWithColorSelector<T: Lerp> {
     pub color_selector: ColorSelector<T>,
     pub gradient: Gradient<T>,
@@ -108,7 +109,7 @@ impl<T: Default + Lerp> GradientWithColorSelector<T> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GradientEnum<T: Lerp> {
     Gradient(Gradient<T>),
     ColorSelector(GradientWithColorSelector<T>),
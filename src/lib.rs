@@ -88,26 +88,38 @@ mod gradient;
 mod modifiers;
 mod plugin;
 mod render;
+mod ron_merge;
 mod spawn;
+mod sub_emitter;
 
 pub mod color_selector;
 pub mod mesh;
+pub mod presets;
 #[cfg(test)]
 mod test_utils;
 mod wgsl_syntex_tools;
 
-pub use asset::EffectAsset;
+pub use asset::{EffectAsset, EffectAssetError, EffectTemplate, EffectValidationError};
 pub use bundle::ParticleEffectBundle;
 pub use gradient::{Gradient, GradientEnum, GradientKey, GradientWithColorSelector};
 pub use modifiers::{
-    AccelModifier, ColorOverLifetimeModifier, ForceFieldModifier, ForceFieldParam, InitModifier,
-    ParticleTextureModifier, PositionCircleModifier, PositionModifier, PositionSphereModifier,
-    RenderModifier, ShapeDimension, SizeOverLifetimeModifier, UpdateModifier, FFNUM,
+    AccelModifier, BillboardMode, BillboardModifier, BoxVelocity, CollisionModifier,
+    CollisionResponse, ColorOverLifetimeModifier, ColorTintModifier, CurlNoiseModifier, CurveKind,
+    CurvePlacement, CurveVelocity, CustomModifier, DragModifier, FlipbookMode, FlipbookModifier,
+    ForceFieldModifier, ForceFieldParam, InitModifier, KillVolumeModifier, LifetimeModifier,
+    MeshSurfaceModifier, Modifier, OrientAlongVelocityModifier, ParticleTextureModifier,
+    PositionBoxModifier, PositionCircleModifier, PositionConeModifier, PositionCurveModifier,
+    PositionModifier, PositionSphereModifier, RenderModifier, RotationModifier,
+    RotationOverLifetimeModifier, SdfShape, ShapeDimension, SizeOverLifetimeModifier,
+    SpeedLimitModifier, SubEmitterModifier, TextureMode, UpdateModifier, VelocityConeModifier,
+    VelocityDirectionModifier, VelocityRadialModifier, VelocityTangentModifier, VortexModifier,
+    FFNUM,
 };
 pub use plugin::HanabiPlugin;
 pub use render::appear_area::*;
 pub use render::EffectCacheId;
 pub use spawn::{Spawner, Value};
+pub use sub_emitter::{SubEmitterOf, MAX_SUB_EMITTER_DEPTH};
 
 #[cfg(not(any(feature = "2d", feature = "3d")))]
 compile_error!("Enable either the '2d' or '3d' feature.");
@@ -206,6 +218,9 @@ pub struct ParticleEffect {
     effect: EffectCacheId,
     /// Particle spawning descriptor.
     spawner: Option<Spawner>,
+    /// Number of particles spawned so far, wrapping around. Lets the particles spawned each
+    /// frame continue the numbering of the previous frames, for [`CurvePlacement::Uniform`].
+    spawn_offset: u32,
 }
 
 impl ParticleEffect {
@@ -215,6 +230,7 @@ impl ParticleEffect {
             handle,
             effect: EffectCacheId::INVALID,
             spawner: None,
+            spawn_offset: 0,
         }
     }
 
@@ -234,6 +250,18 @@ impl ParticleEffect {
         self.spawner.as_mut().unwrap()
     }
 
+    /// Replace the spawner of this particle effect with a fresh copy of the given one, generally
+    /// after the effect asset was reloaded. The appear areas and the active state of the current
+    /// spawner, if any, are preserved since they're controlled at runtime.
+    pub(crate) fn reload_spawner(&mut self, spawner: &Spawner) {
+        let mut new_spawner = spawner.clone();
+        if let Some(old_spawner) = self.spawner.take() {
+            new_spawner.set_appear_areas(old_spawner.get_appear_areas());
+            new_spawner.set_active(old_spawner.is_active());
+        }
+        self.spawner = Some(new_spawner);
+    }
+
     /// Get the spawner of this particle effect.
     ///
     /// Returns None if `with_spawner` was not called
@@ -292,4 +320,17 @@ mod tests {
         let s = Value::Uniform((1.0_f32, 2.0_f32)).to_wgsl_string();
         assert_eq!(s, "rand() * (2. - 1.) + 1.");
     }
+
+    #[test]
+    fn reload_spawner() {
+        let mut effect = ParticleEffect::new(Handle::default());
+        let spawner = effect.spawner(&Spawner::rate(5.0.into()));
+        spawner.set_active(false);
+
+        let new_spawner = Spawner::burst(10.0.into(), 2.0.into());
+        effect.reload_spawner(&new_spawner);
+        let spawner = effect.maybe_spawner().unwrap();
+        assert!(!spawner.is_active());
+        assert_eq!(*spawner, new_spawner.with_active(false));
+    }
 }
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use crate::{
-    asset::{InitLayout, RenderLayout, UpdateLayout},
+    asset::{EffectAsset, InitLayout, RenderLayout, UpdateLayout},
     gradient::{Gradient, GradientEnum},
     ToWgslString, Value,
 };
@@ -27,8 +29,205 @@ pub trait RenderModifier {
     fn apply(&self, render_layout: &mut RenderLayout);
 }
 
+/// Any modifier of an [`EffectAsset`], stored by value in the order it was added.
+///
+/// Each variant wraps one of the concrete modifier types of this crate, which allows the
+/// modifiers of an effect to be serialized, inspected, and edited after the effect was built.
+/// Use [`as_init()`], [`as_update()`], and [`as_render()`] to access the variant through the
+/// trait of the stage it applies to.
+///
+/// Modifiers defined outside of this crate are wrapped in the [`Custom`] variant. Those
+/// can't be serialized, so saving an effect containing one fails.
+///
+/// [`EffectAsset`]: crate::EffectAsset
+/// [`Custom`]: Modifier::Custom
+/// [`as_init()`]: Modifier::as_init
+/// [`as_update()`]: Modifier::as_update
+/// [`as_render()`]: Modifier::as_render
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Modifier {
+    /// See [`PositionCircleModifier`].
+    PositionCircle(PositionCircleModifier),
+    /// See [`PositionModifier`].
+    Position(PositionModifier),
+    /// See [`PositionSphereModifier`].
+    PositionSphere(PositionSphereModifier),
+    /// See [`PositionConeModifier`].
+    PositionCone(PositionConeModifier),
+    /// See [`PositionBoxModifier`].
+    PositionBox(PositionBoxModifier),
+    /// See [`MeshSurfaceModifier`].
+    MeshSurface(MeshSurfaceModifier),
+    /// See [`PositionCurveModifier`].
+    PositionCurve(PositionCurveModifier),
+    /// See [`VelocityRadialModifier`].
+    VelocityRadial(VelocityRadialModifier),
+    /// See [`VelocityTangentModifier`].
+    VelocityTangent(VelocityTangentModifier),
+    /// See [`VelocityDirectionModifier`].
+    VelocityDirection(VelocityDirectionModifier),
+    /// See [`VelocityConeModifier`].
+    VelocityCone(VelocityConeModifier),
+    /// See [`LifetimeModifier`].
+    Lifetime(LifetimeModifier),
+    /// See [`RotationModifier`].
+    Rotation(RotationModifier),
+    /// See [`AccelModifier`].
+    Accel(AccelModifier),
+    /// See [`ForceFieldModifier`].
+    ForceField(ForceFieldModifier),
+    /// See [`DragModifier`].
+    Drag(DragModifier),
+    /// See [`CurlNoiseModifier`].
+    CurlNoise(CurlNoiseModifier),
+    /// See [`VortexModifier`].
+    Vortex(VortexModifier),
+    /// See [`CollisionModifier`].
+    Collision(CollisionModifier),
+    /// See [`KillVolumeModifier`].
+    KillVolume(KillVolumeModifier),
+    /// See [`SpeedLimitModifier`].
+    SpeedLimit(SpeedLimitModifier),
+    /// See [`SubEmitterModifier`].
+    SubEmitter(SubEmitterModifier),
+    /// See [`ParticleTextureModifier`].
+    ParticleTexture(ParticleTextureModifier),
+    /// See [`ColorOverLifetimeModifier`].
+    ColorOverLifetime(ColorOverLifetimeModifier),
+    /// See [`ColorTintModifier`].
+    ColorTint(ColorTintModifier),
+    /// See [`FlipbookModifier`].
+    Flipbook(FlipbookModifier),
+    /// See [`BillboardModifier`].
+    Billboard(BillboardModifier),
+    /// See [`OrientAlongVelocityModifier`].
+    OrientAlongVelocity(OrientAlongVelocityModifier),
+    /// See [`SizeOverLifetimeModifier`].
+    SizeOverLifetime(SizeOverLifetimeModifier),
+    /// See [`RotationOverLifetimeModifier`].
+    RotationOverLifetime(RotationOverLifetimeModifier),
+    /// See [`CustomModifier`].
+    #[serde(skip)]
+    Custom(CustomModifier),
+}
+
+impl Modifier {
+    /// Get the modifier as an [`InitModifier`], if it applies to the init stage.
+    pub fn as_init(&self) -> Option<&dyn InitModifier> {
+        match self {
+            Modifier::PositionCircle(m) => Some(m),
+            Modifier::Position(m) => Some(m),
+            Modifier::PositionSphere(m) => Some(m),
+            Modifier::PositionCone(m) => Some(m),
+            Modifier::PositionBox(m) => Some(m),
+            Modifier::MeshSurface(m) => Some(m),
+            Modifier::PositionCurve(m) => Some(m),
+            Modifier::VelocityRadial(m) => Some(m),
+            Modifier::VelocityTangent(m) => Some(m),
+            Modifier::VelocityDirection(m) => Some(m),
+            Modifier::VelocityCone(m) => Some(m),
+            Modifier::Lifetime(m) => Some(m),
+            Modifier::Rotation(m) => Some(m),
+            Modifier::Custom(CustomModifier::Init(m)) => Some(m.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Get the modifier as an [`UpdateModifier`], if it applies to the update stage.
+    pub fn as_update(&self) -> Option<&dyn UpdateModifier> {
+        match self {
+            Modifier::Accel(m) => Some(m),
+            Modifier::ForceField(m) => Some(m),
+            Modifier::Drag(m) => Some(m),
+            Modifier::CurlNoise(m) => Some(m),
+            Modifier::Vortex(m) => Some(m),
+            Modifier::Collision(m) => Some(m),
+            Modifier::KillVolume(m) => Some(m),
+            Modifier::SpeedLimit(m) => Some(m),
+            Modifier::SubEmitter(m) => Some(m),
+            Modifier::Custom(CustomModifier::Update(m)) => Some(m.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Get the modifier as a [`RenderModifier`], if it applies to the render stage.
+    pub fn as_render(&self) -> Option<&dyn RenderModifier> {
+        match self {
+            Modifier::ParticleTexture(m) => Some(m),
+            Modifier::ColorOverLifetime(m) => Some(m),
+            Modifier::ColorTint(m) => Some(m),
+            Modifier::Flipbook(m) => Some(m),
+            Modifier::Billboard(m) => Some(m),
+            Modifier::OrientAlongVelocity(m) => Some(m),
+            Modifier::SizeOverLifetime(m) => Some(m),
+            Modifier::RotationOverLifetime(m) => Some(m),
+            Modifier::Custom(CustomModifier::Render(m)) => Some(m.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// A modifier defined outside of this crate, applied to the stage of the trait it implements.
+///
+/// Use [`EffectAsset::init_custom()`], [`EffectAsset::update_custom()`], and
+/// [`EffectAsset::render_custom()`] to add one to an effect.
+///
+/// [`EffectAsset::init_custom()`]: crate::EffectAsset::init_custom
+/// [`EffectAsset::update_custom()`]: crate::EffectAsset::update_custom
+/// [`EffectAsset::render_custom()`]: crate::EffectAsset::render_custom
+#[derive(Clone)]
+pub enum CustomModifier {
+    /// Custom initialization modifier.
+    Init(Arc<dyn InitModifier + Send + Sync>),
+    /// Custom update modifier.
+    Update(Arc<dyn UpdateModifier + Send + Sync>),
+    /// Custom render modifier.
+    Render(Arc<dyn RenderModifier + Send + Sync>),
+}
+
+macro_rules! impl_modifier_from {
+    ($t:ty, $variant:ident) => {
+        impl From<$t> for Modifier {
+            fn from(modifier: $t) -> Self {
+                Modifier::$variant(modifier)
+            }
+        }
+    };
+}
+
+impl_modifier_from!(PositionCircleModifier, PositionCircle);
+impl_modifier_from!(PositionModifier, Position);
+impl_modifier_from!(PositionSphereModifier, PositionSphere);
+impl_modifier_from!(PositionConeModifier, PositionCone);
+impl_modifier_from!(PositionBoxModifier, PositionBox);
+impl_modifier_from!(MeshSurfaceModifier, MeshSurface);
+impl_modifier_from!(PositionCurveModifier, PositionCurve);
+impl_modifier_from!(VelocityRadialModifier, VelocityRadial);
+impl_modifier_from!(VelocityTangentModifier, VelocityTangent);
+impl_modifier_from!(VelocityDirectionModifier, VelocityDirection);
+impl_modifier_from!(VelocityConeModifier, VelocityCone);
+impl_modifier_from!(LifetimeModifier, Lifetime);
+impl_modifier_from!(RotationModifier, Rotation);
+impl_modifier_from!(AccelModifier, Accel);
+impl_modifier_from!(ForceFieldModifier, ForceField);
+impl_modifier_from!(DragModifier, Drag);
+impl_modifier_from!(CurlNoiseModifier, CurlNoise);
+impl_modifier_from!(VortexModifier, Vortex);
+impl_modifier_from!(CollisionModifier, Collision);
+impl_modifier_from!(KillVolumeModifier, KillVolume);
+impl_modifier_from!(SpeedLimitModifier, SpeedLimit);
+impl_modifier_from!(SubEmitterModifier, SubEmitter);
+impl_modifier_from!(ParticleTextureModifier, ParticleTexture);
+impl_modifier_from!(ColorOverLifetimeModifier, ColorOverLifetime);
+impl_modifier_from!(ColorTintModifier, ColorTint);
+impl_modifier_from!(FlipbookModifier, Flipbook);
+impl_modifier_from!(BillboardModifier, Billboard);
+impl_modifier_from!(OrientAlongVelocityModifier, OrientAlongVelocity);
+impl_modifier_from!(SizeOverLifetimeModifier, SizeOverLifetime);
+impl_modifier_from!(RotationOverLifetimeModifier, RotationOverLifetime);
+
 /// The dimension of a shape to consider.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ShapeDimension {
     /// Consider the surface of the shape only.
     Surface,
@@ -43,7 +242,7 @@ impl Default for ShapeDimension {
 }
 
 /// An initialization modifier spawning particles on a circle/disc.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PositionCircleModifier {
     /// The circle center, relative to the emitter position.
     pub center: Vec3,
@@ -86,7 +285,7 @@ impl InitModifier for PositionCircleModifier {
             }
         };
 
-        init_layout.position_code = format!(
+        init_layout.add_position_code(&format!(
             r##"
     // >>> [PositionCircleModifier]
     // Circle center
@@ -97,7 +296,7 @@ impl InitModifier for PositionCircleModifier {
     // Circle radius
     {}
     // Radial speed
-    let speed = {};
+    speed = {};
     // Spawn random point on/in circle
     let theta = rand() * tau;
     let dir = tangent * cos(theta) + bitangent * sin(theta);
@@ -111,12 +310,12 @@ impl InitModifier for PositionCircleModifier {
             bitangent.to_wgsl_string(),
             radius_code,
             self.speed.to_wgsl_string()
-        );
+        ));
     }
 }
 
 /// Postison Modifier
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PositionModifier {
     /// The center, relative to the emitter position.
     pub center: Vec3,
@@ -158,7 +357,7 @@ impl InitModifier for PositionModifier {
             }
         };
 
-        init_layout.position_code = format!(
+        init_layout.add_position_code(&format!(
             r##"
     // >>> [PositionCircleModifier]
     // Circle center
@@ -181,13 +380,13 @@ impl InitModifier for PositionModifier {
             tangent.to_wgsl_string(),
             bitangent.to_wgsl_string(),
             radius_code,
-        );
+        ));
     }
 }
 
 
 /// An initialization modifier spawning particles on a sphere.
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
 pub struct PositionSphereModifier {
     /// The sphere center, relative to the emitter position.
     pub center: Vec3,
@@ -216,7 +415,7 @@ impl InitModifier for PositionSphereModifier {
                 )
             }
         };
-        init_layout.position_code = format!(
+        init_layout.add_position_code(&format!(
             r##"
     // >>> [PositionSphereModifier]
     // Sphere center
@@ -240,151 +439,2089 @@ impl InitModifier for PositionSphereModifier {
             self.center.to_wgsl_string(),
             radius_code,
             // self.speed.to_wgsl_string()
-        );
+        ));
     }
 }
 
-/// A modifier modulating each particle's color by sampling a texture.
-#[derive(Default, Clone)]
-pub struct ParticleTextureModifier {
-    /// The texture image to modulate the particle color with.
-    pub texture: Handle<Image>,
+/// An initialization modifier spawning particles on a cone, for directional emission like jets,
+/// muzzle flashes, or fountains.
+///
+/// Particles are spawned between the apex and the base of the cone, and move away from the apex
+/// along the ray passing through their spawn position.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PositionConeModifier {
+    /// The cone apex, relative to the emitter position.
+    pub apex: Vec3,
+    /// The cone axis, which is the normalized direction from the apex to the center of the base.
+    pub axis: Vec3,
+    /// The half-angle of the cone at its apex, in radians. Must be in \[0:π/2\[.
+    pub angle: f32,
+    /// The cone height, from apex to base along the axis.
+    pub height: f32,
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+    /// The shape dimension to spawn from. The surface is the lateral surface of the cone,
+    /// excluding its base.
+    pub dimension: ShapeDimension,
 }
 
-impl RenderModifier for ParticleTextureModifier {
-    fn apply(&self, render_layout: &mut RenderLayout) {
-        render_layout.particle_texture = Some(self.texture.clone());
+impl Default for PositionConeModifier {
+    fn default() -> Self {
+        Self {
+            apex: Default::default(),
+            axis: Vec3::Y,
+            angle: Default::default(),
+            height: Default::default(),
+            speed: Default::default(),
+            dimension: Default::default(),
+        }
     }
 }
 
-/// A modifier modulating each particle's color over its lifetime with a gradient curve.
-#[derive(Clone)]
-pub struct ColorOverLifetimeModifier {
-    /// The color gradient defining the particle color based on its lifetime.
-    pub gradient: GradientEnum<Vec4>,
+impl InitModifier for PositionConeModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        let axis = self.axis.normalize();
+        let (tangent, bitangent) = axis.any_orthonormal_pair();
+
+        let height_code = match self.dimension {
+            ShapeDimension::Surface => {
+                // The cone section perimeter grows linearly with the distance to the apex,
+                // so square-root a uniform distribution to spawn uniformly over the surface.
+                format!(
+                    "let h = sqrt(rand()) * {};\n    let s = 1.;",
+                    self.height.to_wgsl_string()
+                )
+            }
+            ShapeDimension::Volume => {
+                // The cone section area grows quadratically with the distance to the apex,
+                // and the disc area quadratically with the radius.
+                format!(
+                    "let h = pow(rand(), 1./3.) * {};\n    let s = sqrt(rand());",
+                    self.height.to_wgsl_string()
+                )
+            }
+        };
+
+        init_layout.add_position_code(&format!(
+            r##"
+    // >>> [PositionConeModifier]
+    // Cone apex
+    let apex = {0};
+    // Cone basis
+    let axis = {1};
+    let tangent = {2};
+    let bitangent = {3};
+    // Tangent of the cone half-angle
+    let tan_angle = {4};
+    // Distance from apex along axis, and relative distance from axis
+    {5}
+    // Spawn random point on/in cone
+    let theta = rand() * tau;
+    let radial = tangent * cos(theta) + bitangent * sin(theta);
+    let ray = axis + radial * (tan_angle * s);
+    ret.pos = apex + ray * h;
+    // Velocity away from apex
+    speed = {6};
+    ret.vel = normalize(ray) * speed;
+    // <<< [PositionConeModifier]
+"##,
+            self.apex.to_wgsl_string(),
+            axis.to_wgsl_string(),
+            tangent.to_wgsl_string(),
+            bitangent.to_wgsl_string(),
+            self.angle.tan().to_wgsl_string(),
+            height_code,
+            self.speed.to_wgsl_string()
+        ));
+    }
 }
 
-impl ColorOverLifetimeModifier {
-    // init a gradient
-    pub fn new(gradient: GradientEnum<Vec4>) -> Self { Self { gradient } }    
+/// The initial velocity of the particles spawned by a [`PositionBoxModifier`].
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BoxVelocity {
+    /// Particles spawn at rest.
+    Zero,
+    /// Particles move away from the box center.
+    Radial {
+        /// The speed of the particles on spawn.
+        speed: Value<f32>,
+    },
+    /// Particles all move in the same direction.
+    Directional {
+        /// The direction of the particles, in effect space.
+        direction: Vec3,
+        /// The speed of the particles on spawn.
+        speed: Value<f32>,
+    },
 }
 
-impl Default for ColorOverLifetimeModifier {
+impl Default for BoxVelocity {
     fn default() -> Self {
-        Self { gradient: GradientEnum::Gradient(Default::default()) }
+        BoxVelocity::Zero
     }
 }
 
-impl RenderModifier for ColorOverLifetimeModifier {
-    fn apply(&self, render_layout: &mut RenderLayout) {
-        render_layout.lifetime_color_gradient = Some(self.gradient.clone());
+/// An initialization modifier spawning particles on an oriented box.
+///
+/// For an axis-aligned box, leave the rotation to its default identity value.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct PositionBoxModifier {
+    /// The box center, relative to the emitter position.
+    pub center: Vec3,
+    /// The box half size along each of its local axes.
+    pub half_extents: Vec3,
+    /// The box orientation, rotating its local axes into effect space.
+    pub rotation: Quat,
+    /// The initial velocity of the particles.
+    pub velocity: BoxVelocity,
+    /// The shape dimension to spawn from.
+    pub dimension: ShapeDimension,
+}
+
+impl InitModifier for PositionBoxModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        let h = self.half_extents.abs();
+
+        let surface_code = match self.dimension {
+            ShapeDimension::Surface => {
+                // Pick a pair of opposite faces with a probability proportional to their area,
+                // then one of the two faces at random, and project the point onto it.
+                let area_x = h.y * h.z;
+                let area_y = h.x * h.z;
+                let area_z = h.x * h.y;
+                let total = area_x + area_y + area_z;
+                format!(
+                    r##"let face = rand() * {0};
+    let side = select(-1., 1., rand() < 0.5);
+    if (face < {1}) {{
+        p.x = side * half_extents.x;
+    }} else if (face < {2}) {{
+        p.y = side * half_extents.y;
+    }} else {{
+        p.z = side * half_extents.z;
+    }}"##,
+                    total.to_wgsl_string(),
+                    area_x.to_wgsl_string(),
+                    (area_x + area_y).to_wgsl_string()
+                )
+            }
+            ShapeDimension::Volume => String::new(),
+        };
+
+        let velocity_code = match self.velocity {
+            BoxVelocity::Zero => "ret.vel = vec3<f32>(0., 0., 0.);".to_string(),
+            BoxVelocity::Radial { speed } => format!(
+                r##"speed = {};
+    let radial = ret.pos - c;
+    let radial_length = length(radial);
+    ret.vel = select(vec3<f32>(0., 0., 0.), radial / radial_length * speed, radial_length > 0.);"##,
+                speed.to_wgsl_string()
+            ),
+            BoxVelocity::Directional { direction, speed } => format!(
+                "speed = {};\n    ret.vel = {} * speed;",
+                speed.to_wgsl_string(),
+                direction.normalize_or_zero().to_wgsl_string()
+            ),
+        };
+
+        init_layout.add_position_code(&format!(
+            r##"
+    // >>> [PositionBoxModifier]
+    // Box center
+    let c = {0};
+    // Box half extents
+    let half_extents = {1};
+    // Box basis
+    let axis_x = {2};
+    let axis_y = {3};
+    let axis_z = {4};
+    // Spawn random point in box
+    var p = (rand3() * 2. - 1.) * half_extents;
+    // Move point to box surface, if needed
+    {5}
+    ret.pos = c + axis_x * p.x + axis_y * p.y + axis_z * p.z;
+    // Initial velocity
+    {6}
+    // <<< [PositionBoxModifier]
+"##,
+            self.center.to_wgsl_string(),
+            h.to_wgsl_string(),
+            (self.rotation * Vec3::X).to_wgsl_string(),
+            (self.rotation * Vec3::Y).to_wgsl_string(),
+            (self.rotation * Vec3::Z).to_wgsl_string(),
+            surface_code,
+            velocity_code
+        ));
+    }
+}
+
+/// An initialization modifier spawning particles on the surface of a mesh.
+///
+/// Particles are spawned uniformly over the triangles of the mesh, each triangle being picked
+/// with a probability proportional to its area. The mesh must be a triangle list with vertex
+/// positions; vertex normals are optional, and replaced by the triangle normals if missing.
+/// The mesh is read in effect space, without any transform.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct MeshSurfaceModifier {
+    /// The mesh to spawn particles on.
+    #[serde(skip)]
+    pub mesh: Handle<Mesh>,
+    /// Optional asset path of the mesh, saved in place of the handle when the effect is
+    /// serialized.
+    pub mesh_path: Option<String>,
+    /// The speed of the particles on spawn, along the interpolated vertex normal of their spawn
+    /// position. If `None`, the particles spawn at rest.
+    pub normal_speed: Option<Value<f32>>,
+}
+
+impl InitModifier for MeshSurfaceModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        init_layout.mesh_surface = Some(self.mesh.clone());
+
+        let velocity_code = match self.normal_speed {
+            Some(speed) => format!(
+                r##"var n = triangle.n0 * w + triangle.n1 * uv.x + triangle.n2 * uv.y;
+    // Opposite vertex normals can cancel out; use the triangle normal instead
+    if (length(n) <= 0.) {{
+        n = cross(triangle.v1 - triangle.v0, triangle.v2 - triangle.v0);
+    }}
+    let n_length = length(n);
+    speed = {};
+    ret.vel = select(vec3<f32>(0., 0., 0.), n / n_length * speed, n_length > 0.);"##,
+                speed.to_wgsl_string()
+            ),
+            None => "ret.vel = vec3<f32>(0., 0., 0.);".to_string(),
+        };
+
+        init_layout.add_position_code(&format!(
+            r##"
+    // >>> [MeshSurfaceModifier]
+    // Pick a random triangle, weighted by area
+    let triangle = mesh_triangles.triangles[sample_mesh_triangle()];
+    // Spawn random point on triangle
+    var uv = rand2();
+    if (uv.x + uv.y > 1.) {{
+        uv = 1. - uv;
+    }}
+    let w = 1. - uv.x - uv.y;
+    ret.pos = triangle.v0 * w + triangle.v1 * uv.x + triangle.v2 * uv.y;
+    // Initial velocity
+    {}
+    // <<< [MeshSurfaceModifier]
+"##,
+            velocity_code
+        ));
     }
 }
 
+/// The kind of curve a [`PositionCurveModifier`] spawns particles along.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurveKind {
+    /// Straight segments joining each point to the next one. Two points make a single segment.
+    Polyline,
+    /// Catmull-Rom spline passing through all the points.
+    CatmullRom,
+    /// Sequence of cubic Bézier segments. The points are the end point of each segment followed
+    /// by its two control points, and the final end point, so there must be `3 * n + 1` of them
+    /// for `n` segments.
+    Bezier,
+}
 
-/// A modifier modulating each particle's size over its lifetime with a gradient curve.
-#[derive(Default, Clone)]
-pub struct SizeOverLifetimeModifier {
-    /// The size gradient defining the particle size based on its lifetime.
-    pub gradient: Gradient<Vec2>,
+impl Default for CurveKind {
+    fn default() -> Self {
+        CurveKind::Polyline
+    }
 }
 
-impl RenderModifier for SizeOverLifetimeModifier {
-    fn apply(&self, render_layout: &mut RenderLayout) {
-        render_layout.size_color_gradient = Some(self.gradient.clone());
+/// How a [`PositionCurveModifier`] places the particles along its curve.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurvePlacement {
+    /// Particles are spawned at random positions along the curve.
+    Random,
+    /// Particles are spawned at `count` evenly spaced positions along the curve, from start to
+    /// end, cycling through those positions as particles are spawned.
+    ///
+    /// The cycle continues across frames, so a rate spawner emitting a few particles each frame
+    /// still covers all the positions. The particles of a sub-emitter cycle through the
+    /// positions from the first one for each death event of its parent.
+    Uniform {
+        /// Number of evenly spaced spawn positions.
+        count: u32,
+    },
+}
+
+impl Default for CurvePlacement {
+    fn default() -> Self {
+        CurvePlacement::Random
     }
 }
 
-/// A modifier to apply a constant acceleration to all particles each frame.
+/// The initial velocity of the particles spawned by a [`PositionCurveModifier`].
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CurveVelocity {
+    /// Particles spawn at rest.
+    Zero,
+    /// Particles move along the curve tangent, from start to end.
+    Tangent {
+        /// The speed of the particles on spawn.
+        speed: Value<f32>,
+    },
+    /// Particles move perpendicularly to the curve, within the plane normal to `axis`.
+    Normal {
+        /// The axis the velocity is perpendicular to. Set this to `Vec3::Z` for a 2D game.
+        axis: Vec3,
+        /// The speed of the particles on spawn.
+        speed: Value<f32>,
+    },
+}
+
+impl Default for CurveVelocity {
+    fn default() -> Self {
+        CurveVelocity::Zero
+    }
+}
+
+/// An initialization modifier spawning particles along a segment, polyline, or spline.
 ///
-/// This is typically used to apply some kind of gravity.
-#[derive(Default, Clone, Copy)]
-pub struct AccelModifier {
-    /// The constant acceleration to apply to all particles in the effect each frame.
-    pub accel: Vec3,
+/// All curve kinds are converted into a sequence of cubic Bézier segments, whose length is
+/// approximated to distribute the particles evenly along the entire curve.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PositionCurveModifier {
+    /// The kind of curve described by the points.
+    pub kind: CurveKind,
+    /// The curve points, relative to the emitter position.
+    pub points: Vec<Vec3>,
+    /// The placement of the particles along the curve.
+    pub placement: CurvePlacement,
+    /// The initial velocity of the particles.
+    pub velocity: CurveVelocity,
 }
 
-impl UpdateModifier for AccelModifier {
-    fn apply(&self, layout: &mut UpdateLayout) {
-        layout.accel = self.accel;
+impl PositionCurveModifier {
+    /// Check if the number of points is valid for the kind of curve.
+    pub fn has_valid_points(&self) -> bool {
+        let count = self.points.len();
+        match self.kind {
+            CurveKind::Polyline | CurveKind::CatmullRom => count >= 2,
+            CurveKind::Bezier => count >= 4 && count % 3 == 1,
+        }
+    }
+
+    /// Convert the curve into a sequence of cubic Bézier segments, each made of its start point,
+    /// two control points, and end point.
+    fn bezier_segments(&self) -> Vec<[Vec3; 4]> {
+        let points = &self.points;
+        match self.kind {
+            CurveKind::Polyline => points
+                .windows(2)
+                .map(|w| {
+                    let d = (w[1] - w[0]) / 3.;
+                    [w[0], w[0] + d, w[1] - d, w[1]]
+                })
+                .collect(),
+            CurveKind::CatmullRom => (1..points.len())
+                .map(|i| {
+                    // Duplicate the end points as their own neighbors
+                    let p0 = points[i.saturating_sub(2)];
+                    let p1 = points[i - 1];
+                    let p2 = points[i];
+                    let p3 = points[(i + 1).min(points.len() - 1)];
+                    [p1, p1 + (p2 - p0) / 6., p2 - (p3 - p1) / 6., p2]
+                })
+                .collect(),
+            CurveKind::Bezier => points
+                .windows(4)
+                .step_by(3)
+                .map(|w| [w[0], w[1], w[2], w[3]])
+                .collect(),
+        }
     }
 }
 
-/// Parameters for the components making the force field.
-#[derive(Clone, Copy)]
-pub struct ForceFieldParam {
-    /// Position of the source of the force field.
-    pub position: Vec3,
-    /// Maximum radius of the sphere of influence, outside of which
-    /// the force field is null.
-    pub max_radius: f32,
-    /// Minimum radius of the sphere of influence, inside of which
-    /// the force field is null, avoiding the singularity at the source position.
-    pub min_radius: f32,
-    /// The intensity of the force is proportional to mass. Note that the particles_update.wgsl shader will
-    /// ignore all subsequent force field components after it encounters a component with a mass of zero.
-    /// To change the force from an attracting one to a repulsive one, simply set the mass to a negative value.
-    pub mass: f32,
-    /// The force field is proportional to `1 / distance^force_exponent`.
-    pub force_exponent: f32,
-    /// If set to true, the particles that enter within the `min_radius` will conform to a sphere around the
-    /// source position, appearing like a recharging effect.
-    pub conform_to_sphere: bool,
+/// Approximate the length of a cubic Bézier segment by the length of a polyline following it.
+fn bezier_length(b: &[Vec3; 4]) -> f32 {
+    const STEPS: usize = 16;
+    let eval = |u: f32| {
+        let v = 1. - u;
+        b[0] * (v * v * v) + b[1] * (3. * v * v * u) + b[2] * (3. * v * u * u) + b[3] * (u * u * u)
+    };
+    (1..=STEPS)
+        .map(|i| {
+            let u0 = (i - 1) as f32 / STEPS as f32;
+            let u1 = i as f32 / STEPS as f32;
+            eval(u1).distance(eval(u0))
+        })
+        .sum()
 }
 
-impl Default for ForceFieldParam {
+impl InitModifier for PositionCurveModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        let segments = if self.has_valid_points() {
+            self.bezier_segments()
+        } else {
+            // Degenerate curve at the origin, rejected anyway by the asset validation
+            vec![[Vec3::ZERO; 4]]
+        };
+        let lengths: Vec<f32> = segments.iter().map(bezier_length).collect();
+        let total_length: f32 = lengths.iter().sum();
+
+        let placement_code = match self.placement {
+            CurvePlacement::Random => "let s = rand();".to_string(),
+            CurvePlacement::Uniform { count } if count > 1 => format!(
+                "let s = f32(spawn_index % {}u) / {};",
+                count,
+                ((count - 1) as f32).to_wgsl_string()
+            ),
+            CurvePlacement::Uniform { .. } => "let s = 0.;".to_string(),
+        };
+
+        // Select the segment containing the spawn position, and the position within it
+        let mut segment_code = String::new();
+        let mut start = 0.;
+        for (index, (b, length)) in segments.iter().zip(lengths.iter()).enumerate() {
+            let end = start + length;
+            let condition = if index == 0 {
+                format!("if (d <= {}) ", end.to_wgsl_string())
+            } else if index + 1 < segments.len() {
+                format!(" else if (d <= {}) ", end.to_wgsl_string())
+            } else {
+                " else ".to_string()
+            };
+            let condition = if segments.len() == 1 {
+                String::new()
+            } else {
+                condition
+            };
+            let inv_length = if *length > 0. { 1. / length } else { 0. };
+            segment_code += &format!(
+                r##"{}{{
+        b0 = {};
+        b1 = {};
+        b2 = {};
+        b3 = {};
+        u = clamp((d - {}) * {}, 0., 1.);
+    }}"##,
+                condition,
+                b[0].to_wgsl_string(),
+                b[1].to_wgsl_string(),
+                b[2].to_wgsl_string(),
+                b[3].to_wgsl_string(),
+                start.to_wgsl_string(),
+                inv_length.to_wgsl_string()
+            );
+            start = end;
+        }
+
+        let velocity_code = match self.velocity {
+            CurveVelocity::Zero => "ret.vel = vec3<f32>(0., 0., 0.);".to_string(),
+            CurveVelocity::Tangent { speed } => format!(
+                "speed = {};\n    ret.vel = tangent * speed;",
+                speed.to_wgsl_string()
+            ),
+            CurveVelocity::Normal { axis, speed } => format!(
+                r##"speed = {};
+    let normal = cross({}, tangent);
+    let normal_length = length(normal);
+    ret.vel = select(vec3<f32>(0., 0., 0.), normal / normal_length * speed, normal_length > 0.);"##,
+                speed.to_wgsl_string(),
+                axis.normalize_or_zero().to_wgsl_string()
+            ),
+        };
+
+        init_layout.add_position_code(&format!(
+            r##"
+    // >>> [PositionCurveModifier]
+    // Relative position along the curve
+    {0}
+    // Distance along the curve
+    let d = s * {1};
+    // Find Bézier segment
+    var b0: vec3<f32>;
+    var b1: vec3<f32>;
+    var b2: vec3<f32>;
+    var b3: vec3<f32>;
+    var u: f32;
+    {2}
+    // Evaluate segment
+    let v = 1. - u;
+    ret.pos = b0 * (v * v * v) + b1 * (3. * v * v * u) + b2 * (3. * v * u * u) + b3 * (u * u * u);
+    let derivative = (b1 - b0) * (3. * v * v) + (b2 - b1) * (6. * v * u) + (b3 - b2) * (3. * u * u);
+    // The derivative vanishes on duplicate points, or at the end of a segment whose control
+    // point is its end point; fall back to the direction of the whole segment there.
+    let dir = select(b3 - b0, derivative, length(derivative) > 0.);
+    let dir_length = length(dir);
+    let tangent = select(vec3<f32>(0., 0., 0.), dir / dir_length, dir_length > 0.);
+    // Initial velocity
+    {3}
+    // <<< [PositionCurveModifier]
+"##,
+            placement_code,
+            total_length.to_wgsl_string(),
+            segment_code,
+            velocity_code
+        ));
+    }
+}
+
+/// An initialization modifier setting the velocity of particles away from a center point.
+///
+/// Velocity modifiers run after the position modifiers, and override the velocity they set,
+/// so they can be combined with any emission shape. Particles spawned at the center itself get
+/// a zero velocity.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct VelocityRadialModifier {
+    /// The center the particles move away from, relative to the emitter position.
+    pub center: Vec3,
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+}
+
+impl InitModifier for VelocityRadialModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        init_layout.add_velocity_code(&format!(
+            r##"
+    // >>> [VelocityRadialModifier]
+    speed = {1};
+    let radial = ret.pos - {0};
+    let radial_length = length(radial);
+    ret.vel = select(vec3<f32>(0., 0., 0.), radial / radial_length * speed, radial_length > 0.);
+    // <<< [VelocityRadialModifier]
+"##,
+            self.center.to_wgsl_string(),
+            self.speed.to_wgsl_string()
+        ));
+    }
+}
+
+/// An initialization modifier setting the velocity of particles tangentially around an axis,
+/// to make them swirl.
+///
+/// Particles on the axis itself get a zero velocity.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VelocityTangentModifier {
+    /// A point on the rotation axis, relative to the emitter position.
+    pub origin: Vec3,
+    /// The rotation axis. Particles rotate counter-clockwise when looking down the axis.
+    pub axis: Vec3,
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+}
+
+impl Default for VelocityTangentModifier {
     fn default() -> Self {
-        // defaults to no force field (a mass of 0)
-        ForceFieldParam {
-            position: Vec3::new(0., 0., 0.),
-            min_radius: 0.1,
-            max_radius: 0.0,
-            mass: 0.,
-            force_exponent: 0.0,
-            conform_to_sphere: false,
+        Self {
+            origin: Default::default(),
+            axis: Vec3::Y,
+            speed: Default::default(),
         }
     }
 }
 
-/// A modifier to apply a force field to all particles each frame. The force field is made up of
-/// point sources, also called 'components'. The maximum number of components is set with [`FFNUM`].
-#[derive(Default, Clone, Copy)]
-pub struct ForceFieldModifier {
-    /// Array of force field components.
-    pub force_field: [ForceFieldParam; FFNUM],
+impl InitModifier for VelocityTangentModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        init_layout.add_velocity_code(&format!(
+            r##"
+    // >>> [VelocityTangentModifier]
+    speed = {2};
+    let tangent = cross({1}, ret.pos - {0});
+    let tangent_length = length(tangent);
+    ret.vel = select(vec3<f32>(0., 0., 0.), tangent / tangent_length * speed, tangent_length > 0.);
+    // <<< [VelocityTangentModifier]
+"##,
+            self.origin.to_wgsl_string(),
+            self.axis.normalize_or_zero().to_wgsl_string(),
+            self.speed.to_wgsl_string()
+        ));
+    }
 }
 
-impl ForceFieldModifier {
-    /// Instantiate a ForceFieldModifier.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the number of sources exceeds [`FFNUM`].
-    pub fn new<T>(point_attractors: T) -> Self
-    where
-        T: IntoIterator<Item = ForceFieldParam>,
-    {
-        let mut force_field = [ForceFieldParam::default(); FFNUM];
+/// Generate the code setting `ret.vel` to a random direction within `angle` radians of `axis`,
+/// with a polar angle given by `cos_theta_code`.
+fn spread_velocity_code(axis: Vec3, cos_theta_code: &str, speed: Value<f32>) -> String {
+    let axis = axis.normalize_or_zero();
+    let (tangent, bitangent) = axis.any_orthonormal_pair();
+    format!(
+        r##"speed = {0};
+    {1}
+    let sin_theta = sqrt(max(1. - cos_theta * cos_theta, 0.));
+    let phi = rand() * tau;
+    let dir = {2} * (sin_theta * cos(phi)) + {3} * (sin_theta * sin(phi)) + {4} * cos_theta;
+    ret.vel = dir * speed;"##,
+        speed.to_wgsl_string(),
+        cos_theta_code,
+        tangent.to_wgsl_string(),
+        bitangent.to_wgsl_string(),
+        axis.to_wgsl_string()
+    )
+}
 
-        for (i, p_attractor) in point_attractors.into_iter().enumerate() {
-            if i > FFNUM {
-                panic!("Too many point attractors");
-            }
-            force_field[i] = p_attractor;
+/// An initialization modifier setting the velocity of particles along a fixed direction, with
+/// an optional angular spread.
+///
+/// The angle between the particle velocity and the direction is picked uniformly in
+/// \[0:`spread`\], so particles concentrate around the direction. See
+/// [`VelocityConeModifier`] for directions uniformly distributed inside a cone.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VelocityDirectionModifier {
+    /// The main direction of the particles.
+    pub direction: Vec3,
+    /// The maximum angle between the particle velocity and `direction`, in radians.
+    pub spread: f32,
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+}
+
+impl Default for VelocityDirectionModifier {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::Y,
+            spread: Default::default(),
+            speed: Default::default(),
         }
+    }
+}
 
-        Self { force_field }
+impl InitModifier for VelocityDirectionModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        let cos_theta_code = format!(
+            "let cos_theta = cos(rand() * {});",
+            self.spread.to_wgsl_string()
+        );
+        init_layout.add_velocity_code(&format!(
+            r##"
+    // >>> [VelocityDirectionModifier]
+    {}
+    // <<< [VelocityDirectionModifier]
+"##,
+            spread_velocity_code(self.direction, &cos_theta_code, self.speed)
+        ));
     }
+}
 
-    /// Perhaps will be deleted in the future.
-    // Delete me?
-    pub fn add_or_replace(&mut self, point_attractor: ForceFieldParam, index: usize) {
-        self.force_field[index] = point_attractor;
+/// An initialization modifier setting the velocity of particles to a random direction
+/// uniformly distributed inside a cone.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VelocityConeModifier {
+    /// The cone axis.
+    pub axis: Vec3,
+    /// The half-angle of the cone at its apex, in radians.
+    pub angle: f32,
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+}
+
+impl Default for VelocityConeModifier {
+    fn default() -> Self {
+        Self {
+            axis: Vec3::Y,
+            angle: Default::default(),
+            speed: Default::default(),
+        }
     }
 }
 
-impl UpdateModifier for ForceFieldModifier {
-    fn apply(&self, layout: &mut UpdateLayout) {
-        layout.force_field = self.force_field;
+impl InitModifier for VelocityConeModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        // Uniform distribution over the spherical cap, by picking the cosine uniformly
+        let cos_theta_code = format!(
+            "let cos_theta = 1. - rand() * {};",
+            (1. - self.angle.cos()).to_wgsl_string()
+        );
+        init_layout.add_velocity_code(&format!(
+            r##"
+    // >>> [VelocityConeModifier]
+    {}
+    // <<< [VelocityConeModifier]
+"##,
+            spread_velocity_code(self.axis, &cos_theta_code, self.speed)
+        ));
+    }
+}
+
+/// An initialization modifier setting the lifetime of each particle.
+///
+/// With a [`Value::Uniform`] lifetime, each particle gets its own random lifetime, which avoids
+/// particles dying in sync. Without this modifier, all particles use the lifetime set with
+/// [`Spawner::set_particles_live_time()`].
+///
+/// [`Spawner::set_particles_live_time()`]: crate::Spawner::set_particles_live_time
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct LifetimeModifier {
+    /// The particle lifetime, in seconds.
+    pub lifetime: Value<f32>,
+}
+
+impl Default for LifetimeModifier {
+    fn default() -> Self {
+        Self {
+            lifetime: Value::Single(5.),
+        }
+    }
+}
+
+impl InitModifier for LifetimeModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        init_layout.lifetime_code = format!(
+            r##"
+    // >>> [LifetimeModifier]
+    return {};
+    // <<< [LifetimeModifier]
+"##,
+            self.lifetime.to_wgsl_string()
+        );
+    }
+}
+
+/// An initialization modifier setting the rotation and angular velocity of newly emitted
+/// particles.
+///
+/// The rotation turns the particle quad in its plane, counter-clockwise, after it's oriented by
+/// the [`BillboardMode`] of the effect. It's integrated each frame from the angular velocity.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct RotationModifier {
+    /// The initial rotation of the particles, in radians.
+    pub rotation: Value<f32>,
+    /// The angular velocity of the particles, in radians per second.
+    pub angular_velocity: Value<f32>,
+}
+
+impl InitModifier for RotationModifier {
+    fn apply(&self, init_layout: &mut InitLayout) {
+        init_layout.rotation_code = format!(
+            r##"
+    // >>> [RotationModifier]
+    let rotation = {};
+    let angular_velocity = {};
+    return vec2<f32>(rotation, angular_velocity);
+    // <<< [RotationModifier]
+"##,
+            self.rotation.to_wgsl_string(),
+            self.angular_velocity.to_wgsl_string()
+        );
+    }
+}
+
+/// How the texture of a [`ParticleTextureModifier`] is combined with the particle color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureMode {
+    /// The texture color, including its alpha, multiplies the particle color.
+    RgbaMultiply,
+    /// The red channel of the texture multiplies the particle alpha, for greyscale masks.
+    AlphaFromRed,
+    /// The luminance of the texture color multiplies the particle alpha, for greyscale masks
+    /// stored as colors.
+    AlphaFromLuminance,
+    /// Like [`TextureMode::RgbaMultiply`], for textures whose color is premultiplied by their
+    /// alpha. The color is divided back by the alpha before blending.
+    Premultiplied,
+}
+
+impl Default for TextureMode {
+    fn default() -> Self {
+        TextureMode::AlphaFromRed
+    }
+}
+
+/// A modifier modulating each particle's color by sampling a texture.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ParticleTextureModifier {
+    /// The texture image to modulate the particle color with.
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+    /// Optional asset path of the texture, saved in place of the handle when the
+    /// effect is serialized.
+    pub texture_path: Option<String>,
+    /// How the texture is combined with the particle color.
+    #[serde(default)]
+    pub mode: TextureMode,
+}
+
+impl RenderModifier for ParticleTextureModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.particle_texture = Some(self.texture.clone());
+        render_layout.texture_mode = self.mode;
+    }
+}
+
+/// A modifier modulating each particle's color over its lifetime with a gradient curve.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ColorOverLifetimeModifier {
+    /// The color gradient defining the particle color based on its lifetime.
+    pub gradient: GradientEnum<Vec4>,
+}
+
+impl ColorOverLifetimeModifier {
+    // init a gradient
+    pub fn new(gradient: GradientEnum<Vec4>) -> Self { Self { gradient } }    
+}
+
+impl Default for ColorOverLifetimeModifier {
+    fn default() -> Self {
+        Self { gradient: GradientEnum::Gradient(Default::default()) }
+    }
+}
+
+impl RenderModifier for ColorOverLifetimeModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout
+            .lifetime_color_gradients
+            .push(self.gradient.clone());
+    }
+}
+
+/// A modifier multiplying the color of all particles by a constant color.
+///
+/// Several tints stack by multiplying their colors. A tint also applies on top of any
+/// [`ColorOverLifetimeModifier`], regardless of their order.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ColorTintModifier {
+    /// The color to multiply the particle color with.
+    pub color: Vec4,
+}
+
+impl Default for ColorTintModifier {
+    fn default() -> Self {
+        Self { color: Vec4::ONE }
+    }
+}
+
+impl RenderModifier for ColorTintModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        let tint = render_layout.color_tint.unwrap_or(Vec4::ONE);
+        render_layout.color_tint = Some(tint * self.color);
+    }
+}
+
+
+/// How the frame of a [`FlipbookModifier`] is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FlipbookMode {
+    /// The animation plays exactly once over the particle lifetime.
+    OverLifetime,
+    /// The animation plays at a fixed frame rate, looping.
+    Fps {
+        /// Number of frames per second.
+        fps: f32,
+    },
+    /// A random frame is picked when the particle is emitted, and kept over its lifetime.
+    Random,
+}
+
+impl Default for FlipbookMode {
+    fn default() -> Self {
+        FlipbookMode::OverLifetime
+    }
+}
+
+/// A modifier animating the particle texture as a sprite sheet, also called flipbook.
+///
+/// The texture of the [`ParticleTextureModifier`] is split into a grid of `columns` by `rows`
+/// frames, read from left to right then from top to bottom. Each particle displays one frame at
+/// a time, chosen according to the [`FlipbookMode`]. This modifier has no effect on particles
+/// without a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlipbookModifier {
+    /// Number of frames along the width of the texture.
+    pub columns: u32,
+    /// Number of frames along the height of the texture.
+    pub rows: u32,
+    /// How the frame displayed by each particle is chosen.
+    pub mode: FlipbookMode,
+    /// If `true`, blend each frame with the next one, for a smoother animation. This samples
+    /// the texture twice.
+    pub blend: bool,
+}
+
+impl Default for FlipbookModifier {
+    fn default() -> Self {
+        Self {
+            columns: 1,
+            rows: 1,
+            mode: FlipbookMode::default(),
+            blend: false,
+        }
+    }
+}
+
+impl RenderModifier for FlipbookModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.flipbook = Some(*self);
+    }
+}
+
+/// The orientation of the particle quads.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BillboardMode {
+    /// The quads face the camera position, so they look the same from any angle, even on the
+    /// edges of a wide field of view.
+    FaceCamera,
+    /// The quads are parallel to the camera plane, and aligned with the camera axes.
+    FaceCameraPlane,
+    /// The quads only rotate around a world axis to face the camera, like a cylinder. This
+    /// keeps grass or fire upright.
+    AxisAligned {
+        /// The world axis the Y axis of the quads is aligned with.
+        axis: Vec3,
+    },
+    /// The quads have a fixed world orientation, facing the +Z axis before rotation.
+    Fixed {
+        /// The rotation of the quads.
+        rotation: Quat,
+    },
+}
+
+impl Default for BillboardMode {
+    fn default() -> Self {
+        BillboardMode::FaceCameraPlane
+    }
+}
+
+impl BillboardMode {
+    /// Generate the code setting the axes `axis_x` and `axis_y` of the particle quad.
+    pub(crate) fn to_orientation_code(&self) -> String {
+        match *self {
+            BillboardMode::FaceCamera => r##"
+    // Face the camera position
+    let forward = view.world_position - particle.pos;
+    let right = cross(view.inverse_view[1].xyz, forward);
+    if (length(right) > 0.) {
+        axis_x = normalize(right);
+        axis_y = normalize(cross(forward, axis_x));
+    }
+"##
+            .to_string(),
+            BillboardMode::FaceCameraPlane => r##"
+    // Face the camera plane
+    axis_x = view.inverse_view[0].xyz;
+    axis_y = view.inverse_view[1].xyz;
+"##
+            .to_string(),
+            BillboardMode::AxisAligned { axis } => format!(
+                r##"
+    // Rotate around a world axis to face the camera
+    axis_y = {};
+    let right = cross(axis_y, view.world_position - particle.pos);
+    if (length(right) > 0.) {{
+        axis_x = normalize(right);
+    }}
+"##,
+                axis.normalize_or_zero().to_wgsl_string()
+            ),
+            BillboardMode::Fixed { rotation } => format!(
+                r##"
+    // Fixed world orientation
+    axis_x = {};
+    axis_y = {};
+"##,
+                (rotation * Vec3::X).to_wgsl_string(),
+                (rotation * Vec3::Y).to_wgsl_string()
+            ),
+        }
+    }
+}
+
+/// A modifier choosing the orientation of the particle quads.
+///
+/// Without this modifier, the quads face the camera plane. An
+/// [`OrientAlongVelocityModifier`] takes precedence over the billboard mode for the particles
+/// in motion.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct BillboardModifier {
+    /// The orientation of the particle quads.
+    pub mode: BillboardMode,
+}
+
+impl RenderModifier for BillboardModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.billboard = self.mode;
+    }
+}
+
+/// A modifier aligning each particle along its velocity, to draw sparks, rain streaks, or
+/// tracers.
+///
+/// The X axis of the particle quad follows the velocity, while the quad is rotated around it to
+/// face the camera. The quad is stretched along its velocity by `1 + stretch * speed`. Particles
+/// at rest keep the orientation of the [`BillboardMode`] of the effect.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct OrientAlongVelocityModifier {
+    /// Stretch factor of the particles along their velocity, per unit of speed.
+    pub stretch: f32,
+}
+
+impl RenderModifier for OrientAlongVelocityModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.orient_along_velocity = Some(self.stretch);
+    }
+}
+
+/// A modifier modulating each particle's size over its lifetime with a gradient curve.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct SizeOverLifetimeModifier {
+    /// The size gradient defining the particle size based on its lifetime.
+    pub gradient: Gradient<Vec2>,
+}
+
+impl RenderModifier for SizeOverLifetimeModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.size_gradients.push(self.gradient.clone());
+    }
+}
+
+/// A modifier adding a rotation to each particle over its lifetime with a gradient curve.
+///
+/// The rotation of the gradient, in radians, is added to the rotation integrated from the
+/// angular velocity of the particle. See [`RotationModifier`].
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct RotationOverLifetimeModifier {
+    /// The rotation gradient defining the particle rotation based on its lifetime.
+    pub gradient: Gradient<f32>,
+}
+
+impl RenderModifier for RotationOverLifetimeModifier {
+    fn apply(&self, render_layout: &mut RenderLayout) {
+        render_layout.rotation_gradients.push(self.gradient.clone());
+    }
+}
+
+/// A modifier to apply a constant acceleration to all particles each frame.
+///
+/// This is typically used to apply some kind of gravity. The accelerations of several
+/// modifiers add up.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct AccelModifier {
+    /// The constant acceleration to apply to all particles in the effect each frame.
+    pub accel: Vec3,
+}
+
+impl UpdateModifier for AccelModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        layout.accel += self.accel;
+    }
+}
+
+/// A modifier slowing down particles with a drag opposed to their velocity.
+///
+/// The drag deceleration is `(linear + quadratic * speed) * speed`, so the linear term dominates
+/// at low speed and the quadratic one at high speed, like the air resistance of small and large
+/// objects respectively. A particle under a constant acceleration reaches a terminal velocity
+/// instead of accelerating forever. The drag of a single frame never exceeds the particle
+/// velocity, so large coefficients stop particles without making them move backward.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct DragModifier {
+    /// Linear drag coefficient, in 1/s.
+    pub linear: f32,
+    /// Quadratic drag coefficient, in 1/m.
+    pub quadratic: f32,
+}
+
+impl UpdateModifier for DragModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        layout.accel_code += &format!(
+            r##"
+    // >>> [DragModifier]
+    {{
+    let drag = min({} + {} * length(vVel), 1. / sim_params.dt);
+    modifiers_accel = modifiers_accel - vVel * drag;
+    }}
+    // <<< [DragModifier]
+"##,
+            self.linear.to_wgsl_string(),
+            self.quadratic.to_wgsl_string()
+        );
+    }
+}
+
+/// A modifier perturbing the motion of particles with a turbulent curl noise field.
+///
+/// The field is the curl of a 3D gradient noise, so it's divergence-free: particles swirl
+/// around without converging to or diverging from any point, which gives an organic motion to
+/// smoke, magic, or embers. Several octaves of noise, each with twice the frequency and half
+/// the amplitude of the previous one, add smaller details to the motion.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CurlNoiseModifier {
+    /// Frequency of the first noise octave, in 1/m. Smaller values give larger swirls.
+    pub frequency: f32,
+    /// Amplitude of the first noise octave, as an acceleration.
+    pub amplitude: f32,
+    /// Number of noise octaves, in \[1:[`CurlNoiseModifier::MAX_OCTAVES`]\].
+    pub octaves: u32,
+    /// Velocity of the noise field itself, to make it scroll over time.
+    pub scroll: Vec3,
+    /// Seed of the noise, to get different fields for different effects.
+    pub seed: u32,
+}
+
+impl CurlNoiseModifier {
+    /// Maximum number of noise octaves.
+    pub const MAX_OCTAVES: u32 = 8;
+}
+
+impl Default for CurlNoiseModifier {
+    fn default() -> Self {
+        Self {
+            frequency: 1.,
+            amplitude: 1.,
+            octaves: 1,
+            scroll: Vec3::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+impl UpdateModifier for CurlNoiseModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        layout.accel_code += &format!(
+            r##"
+    // >>> [CurlNoiseModifier]
+    {{
+    var noise_pos = (vPos - {0} * sim_params.time) * {1};
+    var noise_amplitude = {2};
+    for (var octave: u32 = 0u; octave < {3}u; octave = octave + 1u) {{
+        modifiers_accel = modifiers_accel + curl_noise(noise_pos, {4}u + octave * 3u) * noise_amplitude;
+        noise_pos = noise_pos * 2.;
+        noise_amplitude = noise_amplitude * 0.5;
+    }}
+    }}
+    // <<< [CurlNoiseModifier]
+"##,
+            self.scroll.to_wgsl_string(),
+            self.frequency.to_wgsl_string(),
+            self.amplitude.to_wgsl_string(),
+            self.octaves.min(Self::MAX_OCTAVES),
+            self.seed
+        );
+    }
+}
+
+/// A modifier making particles orbit around an axis, like in a tornado or a whirlpool.
+///
+/// Inside the area of influence, the velocity of the particles around the axis converges toward
+/// `speed / distance^falloff_exponent`, at a rate set by the `stiffness`, and the particles get
+/// the centripetal acceleration needed to keep them on a circular orbit at their current
+/// speed. An optional inward pull makes them spiral toward the axis, or away from it if
+/// negative. Like for [`ForceFieldParam`], the area of influence is bounded by a
+/// minimum and a maximum radius, here measured from the axis.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VortexModifier {
+    /// A point on the vortex axis, in world space.
+    pub position: Vec3,
+    /// The vortex axis. Particles orbit counter-clockwise when looking down the axis.
+    pub axis: Vec3,
+    /// Maximum distance to the axis, outside of which the vortex has no effect. Can be
+    /// infinite.
+    pub max_radius: f32,
+    /// Minimum distance to the axis, inside of which the vortex has no effect, avoiding the
+    /// singularity on the axis.
+    pub min_radius: f32,
+    /// The orbital speed of the particles, before falloff. Negative values reverse the
+    /// rotation.
+    pub speed: f32,
+    /// How fast the orbital velocity of the particles converges toward `speed`, in 1/s. The
+    /// tangential acceleration is the difference between the target and the current orbital
+    /// velocity times this rate, so other forces can still act on the particles.
+    pub stiffness: f32,
+    /// The acceleration pulling the particles toward the axis, before falloff.
+    pub pull: f32,
+    /// The speed and pull of the vortex are proportional to `1 / distance^falloff_exponent`.
+    pub falloff_exponent: f32,
+}
+
+impl Default for VortexModifier {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            axis: Vec3::Y,
+            max_radius: f32::INFINITY,
+            min_radius: 0.1,
+            speed: 1.,
+            stiffness: 4.,
+            pull: 0.,
+            falloff_exponent: 0.,
+        }
+    }
+}
+
+impl UpdateModifier for VortexModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        let max_radius_code = if self.max_radius.is_finite() {
+            format!(" && r < {}", self.max_radius.to_wgsl_string())
+        } else {
+            String::new()
+        };
+        layout.accel_code += &format!(
+            r##"
+    // >>> [VortexModifier]
+    {{
+    let axis = {0};
+    let rel = vPos - {1};
+    let radial = rel - axis * dot(rel, axis);
+    let r = length(radial);
+    if (r > 0. && r >= {2}{3}) {{
+        let r_hat = radial / r;
+        let t_hat = cross(axis, r_hat);
+        let falloff = 1. / pow(r, {4});
+        let orbital_speed = {5} * falloff;
+        // Steer the orbital velocity toward its target, without overshooting it in a single
+        // step, and keep the particle on its orbit.
+        let tangential_speed = dot(vVel, t_hat);
+        let response = min({7}, 1. / max(sim_params.dt, 0.0001));
+        let tangential_accel = (orbital_speed - tangential_speed) * response;
+        let centripetal_accel = tangential_speed * tangential_speed / r + {6} * falloff;
+        modifiers_accel = modifiers_accel + t_hat * tangential_accel - r_hat * centripetal_accel;
+    }}
+    }}
+    // <<< [VortexModifier]
+"##,
+            self.axis.normalize_or_zero().to_wgsl_string(),
+            self.position.to_wgsl_string(),
+            self.min_radius.to_wgsl_string(),
+            max_radius_code,
+            self.falloff_exponent.to_wgsl_string(),
+            self.speed.to_wgsl_string(),
+            self.pull.to_wgsl_string(),
+            self.stiffness.to_wgsl_string()
+        );
+    }
+}
+
+/// A simple shape described by its signed distance field, in world space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SdfShape {
+    /// An infinite plane, whose inside is the half-space behind its normal.
+    Plane {
+        /// A point on the plane.
+        point: Vec3,
+        /// The plane normal, pointing outside.
+        normal: Vec3,
+    },
+    /// A solid sphere.
+    Sphere {
+        /// The sphere center.
+        center: Vec3,
+        /// The sphere radius.
+        radius: f32,
+    },
+    /// A solid axis-aligned box.
+    Box {
+        /// The box center.
+        center: Vec3,
+        /// The half size of the box along each axis.
+        half_extents: Vec3,
+    },
+}
+
+impl SdfShape {
+    /// Generate the code declaring the signed distance `d` from the particle position `vPos`
+    /// to the shape, negative inside it, and the outward normal `n` of the closest point of
+    /// the shape surface.
+    pub(crate) fn to_sdf_code(&self) -> String {
+        match *self {
+            SdfShape::Plane { point, normal } => format!(
+                r##"let n = {0};
+    let d = dot(vPos - {1}, n);"##,
+                normal.normalize_or_zero().to_wgsl_string(),
+                point.to_wgsl_string()
+            ),
+            SdfShape::Sphere { center, radius } => format!(
+                r##"let rel = vPos - {0};
+    let dist = length(rel);
+    let d = dist - {1};
+    let n = select(vec3<f32>(0., 1., 0.), rel / dist, dist > 0.);"##,
+                center.to_wgsl_string(),
+                radius.to_wgsl_string()
+            ),
+            SdfShape::Box {
+                center,
+                half_extents,
+            } => format!(
+                r##"let rel = vPos - {0};
+    let q = abs(rel) - {1};
+    let q_out = max(q, vec3<f32>(0., 0., 0.));
+    let d = length(q_out) + min(max(q.x, max(q.y, q.z)), 0.);
+    var n = vec3<f32>(0., 0., 0.);
+    if (d > 0.) {{
+        n = normalize(q_out * sign(rel));
+    }} else if (q.x >= q.y && q.x >= q.z) {{
+        n.x = sign(rel.x);
+    }} else if (q.y >= q.z) {{
+        n.y = sign(rel.y);
+    }} else {{
+        n.z = sign(rel.z);
+    }}"##,
+                center.to_wgsl_string(),
+                half_extents.abs().to_wgsl_string()
+            ),
+        }
+    }
+}
+
+/// What happens to a particle colliding with a shape of a [`CollisionModifier`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CollisionResponse {
+    /// The particle is pushed back to the surface of the shape, and bounces off it.
+    Bounce {
+        /// Fraction of the velocity along the surface normal kept after the bounce, in
+        /// \[0:1\]. Zero makes particles stick to the surface, one makes them bounce without
+        /// losing any energy.
+        restitution: f32,
+        /// Fraction of the velocity along the surface lost on contact, in \[0:1\]. Zero makes
+        /// particles slide freely, one stops them.
+        friction: f32,
+    },
+    /// The particle dies on contact.
+    Kill,
+}
+
+impl Default for CollisionResponse {
+    fn default() -> Self {
+        CollisionResponse::Bounce {
+            restitution: 0.5,
+            friction: 0.,
+        }
+    }
+}
+
+/// A modifier colliding particles against a list of shapes.
+///
+/// Collisions are resolved after the particles moved each frame, so fast particles can go
+/// through thin shapes; use planes or large shapes for those. The death of particles killed on
+/// contact triggers any [`SubEmitterModifier`] of the effect at the contact point.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct CollisionModifier {
+    /// The shapes particles collide with.
+    pub shapes: Vec<SdfShape>,
+    /// The response of particles colliding with any of the shapes.
+    pub response: CollisionResponse,
+}
+
+impl UpdateModifier for CollisionModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        let response_code = match self.response {
+            CollisionResponse::Bounce {
+                restitution,
+                friction,
+            } => format!(
+                r##"vPos = vPos - n * d;
+        let vn = dot(vVel, n);
+        if (vn < 0.) {{
+            let vt = vVel - n * vn;
+            vVel = vt * {} - n * (vn * {});
+        }}"##,
+                (1. - friction).to_wgsl_string(),
+                restitution.to_wgsl_string()
+            ),
+            CollisionResponse::Kill => "vAge = vLifetime;".to_string(),
+        };
+        for shape in &self.shapes {
+            layout.post_update_code += &format!(
+                r##"
+    // >>> [CollisionModifier]
+    {{
+    {}
+    if (d < 0.) {{
+        {}
+    }}
+    }}
+    // <<< [CollisionModifier]
+"##,
+                shape.to_sdf_code(),
+                response_code
+            );
+        }
+    }
+}
+
+/// A modifier killing the particles entering or leaving a shape.
+///
+/// Killed particles die like particles reaching the end of their lifetime: their slot is
+/// recycled early for new particles, and their death triggers any [`SubEmitterModifier`] of
+/// the effect. This keeps particles from leaking through walls, or out of a simulation domain.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct KillVolumeModifier {
+    /// The shape of the volume.
+    pub shape: SdfShape,
+    /// If `true`, kill the particles inside the shape; otherwise kill the particles outside
+    /// of it.
+    pub kill_inside: bool,
+}
+
+impl Default for KillVolumeModifier {
+    fn default() -> Self {
+        Self {
+            shape: SdfShape::Plane {
+                point: Vec3::ZERO,
+                normal: Vec3::Y,
+            },
+            kill_inside: true,
+        }
+    }
+}
+
+impl UpdateModifier for KillVolumeModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        layout.post_update_code += &format!(
+            r##"
+    // >>> [KillVolumeModifier]
+    {{
+    {}
+    if (d {} 0.) {{
+        vAge = vLifetime;
+    }}
+    }}
+    // <<< [KillVolumeModifier]
+"##,
+            self.shape.to_sdf_code(),
+            if self.kill_inside { "<" } else { ">" }
+        );
+    }
+}
+
+/// A modifier clamping the speed of particles after the forces are integrated each frame.
+///
+/// This keeps particles from reaching absurd speeds near the sources of a strong force field,
+/// or from stopping completely. Particles at rest keep a zero velocity, whatever the minimum
+/// speed.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SpeedLimitModifier {
+    /// Minimum speed of the particles.
+    pub min_speed: f32,
+    /// Maximum speed of the particles. Can be infinite.
+    pub max_speed: f32,
+    /// Optional maximum absolute value of each component of the particle velocity, applied
+    /// after the speed clamping.
+    pub max_axis_speed: Option<Vec3>,
+}
+
+impl Default for SpeedLimitModifier {
+    fn default() -> Self {
+        Self {
+            min_speed: 0.,
+            max_speed: f32::INFINITY,
+            max_axis_speed: None,
+        }
+    }
+}
+
+impl UpdateModifier for SpeedLimitModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        let max_speed_code = if self.max_speed.is_finite() {
+            format!(
+                "\n    clamped_speed = min(clamped_speed, {});",
+                self.max_speed.to_wgsl_string()
+            )
+        } else {
+            String::new()
+        };
+        let axis_code = match self.max_axis_speed {
+            Some(max_axis_speed) => format!(
+                r##"
+    let max_axis_speed = {};
+    vVel = clamp(vVel, -max_axis_speed, max_axis_speed);"##,
+                max_axis_speed.abs().to_wgsl_string()
+            ),
+            None => String::new(),
+        };
+        layout.post_update_code += &format!(
+            r##"
+    // >>> [SpeedLimitModifier]
+    {{
+    let particle_speed = length(vVel);
+    var clamped_speed = max(particle_speed, {});{}
+    if (particle_speed > 0.) {{
+        vVel = vVel * (clamped_speed / particle_speed);
+    }}{}
+    }}
+    // <<< [SpeedLimitModifier]
+"##,
+            self.min_speed.to_wgsl_string(),
+            max_speed_code,
+            axis_code
+        );
+    }
+}
+
+/// An update modifier spawning the particles of a child effect where the particles of this
+/// effect die.
+///
+/// Each time a particle dies, the update shader records a death event with its position into
+/// a GPU buffer. An instance of the child effect, spawned automatically alongside each instance
+/// of this effect, consumes those events as spawn positions on the GPU, emitting
+/// `particles_per_event` particles for each of them. The child effect ignores its own appear
+/// areas and spawn rate.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct SubEmitterModifier {
+    /// The child effect to spawn on particle death.
+    #[serde(skip)]
+    pub effect: Handle<EffectAsset>,
+    /// Optional asset path of the child effect, saved in place of the handle when the
+    /// effect is serialized.
+    pub effect_path: Option<String>,
+    /// Number of child particles to spawn for each dead particle.
+    pub particles_per_event: u32,
+}
+
+impl UpdateModifier for SubEmitterModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        layout.sub_emitter = Some((self.effect.clone(), self.particles_per_event));
+    }
+}
+
+/// Parameters for the components making the force field.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ForceFieldParam {
+    /// Position of the source of the force field.
+    pub position: Vec3,
+    /// Maximum radius of the sphere of influence, outside of which
+    /// the force field is null.
+    pub max_radius: f32,
+    /// Minimum radius of the sphere of influence, inside of which
+    /// the force field is null, avoiding the singularity at the source position.
+    pub min_radius: f32,
+    /// The intensity of the force is proportional to mass. Note that the particles_update.wgsl shader will
+    /// ignore all subsequent force field components after it encounters a component with a mass of zero.
+    /// To change the force from an attracting one to a repulsive one, simply set the mass to a negative value.
+    pub mass: f32,
+    /// The force field is proportional to `1 / distance^force_exponent`.
+    pub force_exponent: f32,
+    /// If set to true, the particles that enter within the `min_radius` will conform to a sphere around the
+    /// source position, appearing like a recharging effect.
+    pub conform_to_sphere: bool,
+}
+
+impl Default for ForceFieldParam {
+    fn default() -> Self {
+        // defaults to no force field (a mass of 0)
+        ForceFieldParam {
+            position: Vec3::new(0., 0., 0.),
+            min_radius: 0.1,
+            max_radius: 0.0,
+            mass: 0.,
+            force_exponent: 0.0,
+            conform_to_sphere: false,
+        }
+    }
+}
+
+/// A modifier to apply a force field to all particles each frame. The force field is made up of
+/// point sources, also called 'components'. The maximum number of components is set with [`FFNUM`].
+///
+/// The components of several force field modifiers stack, in order, into a single force field,
+/// which must not exceed [`FFNUM`] components in total.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ForceFieldModifier {
+    /// Array of force field components.
+    pub force_field: [ForceFieldParam; FFNUM],
+}
+
+impl ForceFieldModifier {
+    /// Instantiate a ForceFieldModifier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of sources exceeds [`FFNUM`].
+    pub fn new<T>(point_attractors: T) -> Self
+    where
+        T: IntoIterator<Item = ForceFieldParam>,
+    {
+        let mut force_field = [ForceFieldParam::default(); FFNUM];
+
+        for (i, p_attractor) in point_attractors.into_iter().enumerate() {
+            if i >= FFNUM {
+                panic!("Too many point attractors");
+            }
+            force_field[i] = p_attractor;
+        }
+
+        Self { force_field }
+    }
+
+    /// Perhaps will be deleted in the future.
+    // Delete me?
+    pub fn add_or_replace(&mut self, point_attractor: ForceFieldParam, index: usize) {
+        self.force_field[index] = point_attractor;
+    }
+
+    /// Number of components of the force field, up to the first one with a null mass.
+    pub fn source_count(&self) -> usize {
+        self.force_field
+            .iter()
+            .take_while(|source| source.mass != 0.)
+            .count()
+    }
+}
+
+impl UpdateModifier for ForceFieldModifier {
+    fn apply(&self, layout: &mut UpdateLayout) {
+        let start = layout
+            .force_field
+            .iter()
+            .take_while(|source| source.mass != 0.)
+            .count();
+        for (dst, src) in layout.force_field[start..]
+            .iter_mut()
+            .zip(&self.force_field[..self.source_count()])
+        {
+            *dst = *src;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::EffectAssetError;
+
+    /// Build an effect with a valid capacity and spawner, to add the modifiers to test to.
+    fn test_effect() -> EffectAsset {
+        EffectAsset {
+            capacity: 1024,
+            ..Default::default()
+        }
+    }
+
+    fn curve(kind: CurveKind, points: Vec<Vec3>) -> PositionCurveModifier {
+        PositionCurveModifier {
+            kind,
+            points,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bezier_segments() {
+        let a = Vec3::ZERO;
+        let b = Vec3::new(3., 0., 0.);
+        let c = Vec3::new(3., 6., 0.);
+
+        // Each polyline segment is a straight Bézier segment, with its control points at a third
+        // and two thirds of the segment.
+        let segments = curve(CurveKind::Polyline, vec![a, b, c]).bezier_segments();
+        assert_eq!(
+            segments,
+            vec![
+                [a, Vec3::new(1., 0., 0.), Vec3::new(2., 0., 0.), b],
+                [b, Vec3::new(3., 2., 0.), Vec3::new(3., 4., 0.), c],
+            ]
+        );
+
+        // Catmull-Rom segments go through all the points, with a continuous tangent equal to a
+        // sixth of the chord between the neighbors of each point.
+        let segments = curve(CurveKind::CatmullRom, vec![a, b, c]).bezier_segments();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0][0], a);
+        assert_eq!(segments[0][3], b);
+        assert_eq!(segments[1][0], b);
+        assert_eq!(segments[1][3], c);
+        assert_eq!(segments[0][1], a + (b - a) / 6.);
+        assert_eq!(segments[0][3] - segments[0][2], (c - a) / 6.);
+        assert_eq!(segments[1][1] - segments[1][0], (c - a) / 6.);
+        assert_eq!(segments[1][2], c - (c - b) / 6.);
+
+        // Bézier curves share the end point of a segment with the start of the next one
+        let points: Vec<Vec3> = (0..7).map(|i| Vec3::splat(i as f32)).collect();
+        let segments = curve(CurveKind::Bezier, points.clone()).bezier_segments();
+        assert_eq!(
+            segments,
+            vec![
+                [points[0], points[1], points[2], points[3]],
+                [points[3], points[4], points[5], points[6]],
+            ]
+        );
+    }
+
+    #[test]
+    fn bezier_length() {
+        // Straight segment, whatever the spacing of its control points
+        let straight = [
+            Vec3::ZERO,
+            Vec3::new(0.5, 0., 0.),
+            Vec3::new(0.5, 0., 0.),
+            Vec3::new(3., 0., 0.),
+        ];
+        assert!((super::bezier_length(&straight) - 3.).abs() < 1e-5);
+
+        // Usual cubic approximation of a quarter of the unit circle
+        let k = 0.552_284_8;
+        let quarter = [Vec3::X, Vec3::new(1., k, 0.), Vec3::new(k, 1., 0.), Vec3::Y];
+        let length = super::bezier_length(&quarter);
+        assert!((length - std::f32::consts::FRAC_PI_2).abs() < 2e-3);
+    }
+
+    #[test]
+    fn curl_noise() {
+        let noise = CurlNoiseModifier {
+            octaves: 3,
+            seed: 5,
+            ..Default::default()
+        };
+        let mut layout = UpdateLayout::default();
+        noise.apply(&mut layout);
+        // Each octave samples three independent noise fields, so they use distinct seeds
+        assert!(layout.accel_code.contains("octave < 3u;"));
+        assert!(layout
+            .accel_code
+            .contains("curl_noise(noise_pos, 5u + octave * 3u)"));
+        assert!(test_effect().update(noise).validate().is_ok());
+
+        // The octaves are clamped in the shader, in case the effect isn't validated
+        let mut layout = UpdateLayout::default();
+        CurlNoiseModifier {
+            octaves: 100,
+            ..Default::default()
+        }
+        .apply(&mut layout);
+        assert!(layout
+            .accel_code
+            .contains(&format!("octave < {}u;", CurlNoiseModifier::MAX_OCTAVES)));
+
+        for octaves in [0, CurlNoiseModifier::MAX_OCTAVES + 1] {
+            let effect = test_effect().update(CurlNoiseModifier {
+                octaves,
+                ..Default::default()
+            });
+            assert_eq!(
+                effect.validate().unwrap_err().errors,
+                vec![EffectAssetError::InvalidNoiseOctaves {
+                    modifier_index: 0,
+                    octaves
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn drag() {
+        let effect = test_effect()
+            .update(AccelModifier {
+                accel: Vec3::new(0., -9.8, 0.),
+            })
+            .update(DragModifier {
+                linear: 0.5,
+                quadratic: 0.1,
+            });
+        assert!(effect.validate().is_ok());
+        assert!(effect.update_layout().accel_code.contains("[DragModifier]"));
+
+        let effect = test_effect().update(DragModifier {
+            linear: -1.,
+            quadratic: 0.,
+        });
+        assert_eq!(
+            effect.validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidDrag {
+                modifier_index: 0,
+                linear: -1.,
+                quadratic: 0.
+            }]
+        );
+    }
+
+    #[test]
+    fn vortex() {
+        let effect = test_effect().update(VortexModifier::default());
+        assert!(effect.validate().is_ok());
+        let accel_code = effect.update_layout().accel_code;
+        assert!(accel_code.contains("[VortexModifier]"));
+        // An infinite maximum radius is not checked in the shader
+        assert!(!accel_code.contains("r < "));
+        // The orbital velocity converges at a finite rate, not within a single step
+        assert!(accel_code.contains("let response = min(4., 1. / max(sim_params.dt, 0.0001));"));
+
+        let vortex = VortexModifier {
+            min_radius: 2.,
+            max_radius: 1.,
+            ..Default::default()
+        };
+        assert_eq!(
+            test_effect().update(vortex).validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidVortexRadius {
+                modifier_index: 0,
+                min_radius: 2.,
+                max_radius: 1.
+            }]
+        );
+
+        let vortex = VortexModifier {
+            stiffness: f32::INFINITY,
+            ..Default::default()
+        };
+        assert_eq!(
+            test_effect().update(vortex).validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidVortexStiffness {
+                modifier_index: 0,
+                stiffness: f32::INFINITY
+            }]
+        );
+    }
+
+    #[test]
+    fn collision() {
+        let collision = CollisionModifier {
+            shapes: vec![
+                SdfShape::Plane {
+                    point: Vec3::ZERO,
+                    normal: Vec3::Y,
+                },
+                SdfShape::Sphere {
+                    center: Vec3::new(0., 1., 0.),
+                    radius: 0.5,
+                },
+            ],
+            ..Default::default()
+        };
+        let effect = test_effect().update(collision.clone());
+        assert!(effect.validate().is_ok());
+        let post_update_code = effect.update_layout().post_update_code;
+        assert_eq!(post_update_code.matches("[CollisionModifier]").count(), 4);
+        assert!(!post_update_code.contains("vAge = vLifetime;"));
+
+        let kill = CollisionModifier {
+            response: CollisionResponse::Kill,
+            ..collision.clone()
+        };
+        let effect = test_effect().update(kill);
+        assert!(effect
+            .update_layout()
+            .post_update_code
+            .contains("vAge = vLifetime;"));
+
+        let bounce = CollisionModifier {
+            response: CollisionResponse::Bounce {
+                restitution: 1.5,
+                friction: 0.,
+            },
+            ..collision
+        };
+        assert_eq!(
+            test_effect().update(bounce).validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidCollisionResponse {
+                modifier_index: 0,
+                restitution: 1.5,
+                friction: 0.
+            }]
+        );
+    }
+
+    #[test]
+    fn kill_volume() {
+        let effect =
+            test_effect()
+                .update(KillVolumeModifier::default())
+                .update(KillVolumeModifier {
+                    shape: SdfShape::Sphere {
+                        center: Vec3::ZERO,
+                        radius: 10.,
+                    },
+                    kill_inside: false,
+                });
+        assert!(effect.validate().is_ok());
+        let post_update_code = effect.update_layout().post_update_code;
+        assert!(post_update_code.contains("if (d < 0.)"));
+        assert!(post_update_code.contains("if (d > 0.)"));
+    }
+
+    #[test]
+    fn speed_limit() {
+        let effect = test_effect().update(SpeedLimitModifier {
+            max_speed: 10.,
+            ..Default::default()
+        });
+        assert!(effect.validate().is_ok());
+        let post_update_code = effect.update_layout().post_update_code;
+        assert!(post_update_code.contains("min(clamped_speed, 10.)"));
+        assert!(!post_update_code.contains("max_axis_speed"));
+
+        let effect = test_effect().update(SpeedLimitModifier {
+            max_axis_speed: Some(Vec3::new(1., 2., 3.)),
+            ..Default::default()
+        });
+        let post_update_code = effect.update_layout().post_update_code;
+        assert!(!post_update_code.contains("min(clamped_speed"));
+        assert!(post_update_code.contains("max_axis_speed"));
+
+        let effect = test_effect().update(SpeedLimitModifier {
+            min_speed: 2.,
+            max_speed: 1.,
+            ..Default::default()
+        });
+        assert_eq!(
+            effect.validate().unwrap_err().errors,
+            vec![EffectAssetError::InvalidSpeedLimit {
+                modifier_index: 0,
+                min_speed: 2.,
+                max_speed: 1.
+            }]
+        );
+    }
+
+    #[test]
+    fn velocity_modifiers() {
+        let effect = test_effect().init(PositionCircleModifier::default());
+        let position_code = effect.init_layout().position_code;
+        assert!(effect.init_layout().velocity_code.is_empty());
+
+        // Velocity modifiers compose with any shape, without altering its position code
+        let effect = effect.init(VelocityConeModifier {
+            angle: 0.5,
+            speed: 2.0.into(),
+            ..Default::default()
+        });
+        let init_layout = effect.init_layout();
+        assert_eq!(init_layout.position_code, position_code);
+        assert!(init_layout.velocity_code.contains("[VelocityConeModifier]"));
+
+        // Particles spawned at the center of a radial velocity get a zero velocity instead of NaN
+        let effect = effect.init(VelocityRadialModifier::default());
+        let velocity_code = effect.init_layout().velocity_code;
+        assert!(!velocity_code.contains("normalize("));
+        assert!(velocity_code.contains("radial_length > 0."));
+    }
+
+    #[test]
+    fn position_box() {
+        let modifier = PositionBoxModifier {
+            half_extents: Vec3::new(1., -2., 3.),
+            ..Default::default()
+        };
+
+        // Volume: the point is not moved to a face
+        let effect = test_effect().init(PositionBoxModifier {
+            dimension: ShapeDimension::Volume,
+            ..modifier
+        });
+        let position_code = effect.init_layout().position_code;
+        assert!(position_code.contains("let half_extents = vec3<f32>(1., 2., 3.);"));
+        assert!(!position_code.contains("let face"));
+        assert!(position_code.contains("ret.vel = vec3<f32>(0., 0., 0.);"));
+
+        // Surface: the pairs of faces are picked according to their area, 2x3 for the faces
+        // along X, 1x3 along Y, and 1x2 along Z.
+        let effect = test_effect().init(PositionBoxModifier {
+            dimension: ShapeDimension::Surface,
+            velocity: BoxVelocity::Radial { speed: 3.0.into() },
+            ..modifier
+        });
+        let position_code = effect.init_layout().position_code;
+        assert!(position_code.contains("let face = rand() * 11.;"));
+        assert!(position_code.contains("if (face < 6.) {"));
+        assert!(position_code.contains("} else if (face < 9.) {"));
+        assert!(position_code.contains("speed = 3.;"));
+        assert!(position_code.contains("radial_length > 0."));
+
+        let effect = test_effect().init(PositionBoxModifier {
+            velocity: BoxVelocity::Directional {
+                direction: Vec3::new(0., 0., 2.),
+                speed: 4.0.into(),
+            },
+            ..modifier
+        });
+        let position_code = effect.init_layout().position_code;
+        assert!(position_code.contains("ret.vel = vec3<f32>(0., 0., 1.) * speed;"));
+        assert!(position_code.contains("speed = 4.;"));
+    }
+
+    #[test]
+    fn orient_along_velocity() {
+        let effect = test_effect();
+        assert_eq!(effect.render_layout().orient_along_velocity, None);
+        let effect = effect.render(OrientAlongVelocityModifier { stretch: 0.2 });
+        assert_eq!(effect.render_layout().orient_along_velocity, Some(0.2));
+    }
+
+    #[test]
+    fn billboard() {
+        let effect = test_effect();
+        assert_eq!(
+            effect.render_layout().billboard,
+            BillboardMode::FaceCameraPlane
+        );
+        let mode = BillboardMode::AxisAligned { axis: Vec3::Y };
+        let effect = effect.render(BillboardModifier { mode });
+        assert_eq!(effect.render_layout().billboard, mode);
+    }
+
+    #[test]
+    fn rotation() {
+        let effect = test_effect();
+        assert!(effect.init_layout().rotation_code.is_empty());
+        assert!(effect.render_layout().rotation_gradients.is_empty());
+
+        let mut gradient = Gradient::new();
+        gradient.add_key(0.0, 0.);
+        gradient.add_key(1.0, std::f32::consts::PI);
+        let effect = effect
+            .init(RotationModifier {
+                rotation: Value::Uniform((0., std::f32::consts::TAU)),
+                angular_velocity: 2.0.into(),
+            })
+            .render(RotationOverLifetimeModifier { gradient });
+        assert!(effect.validate().is_ok());
+        assert!(effect
+            .init_layout()
+            .rotation_code
+            .contains("[RotationModifier]"));
+        assert_eq!(effect.render_layout().rotation_gradients.len(), 1);
+    }
+
+    #[test]
+    fn flipbook() {
+        let flipbook = FlipbookModifier {
+            columns: 4,
+            rows: 2,
+            mode: FlipbookMode::Fps { fps: 24. },
+            blend: true,
+        };
+        let base = test_effect();
+        let effect = base.clone().render(flipbook);
+        assert!(effect.validate().is_ok());
+        assert_eq!(effect.render_layout().flipbook, Some(flipbook));
+
+        let effect = base.render(FlipbookModifier {
+            rows: 0,
+            mode: FlipbookMode::Fps { fps: 0. },
+            ..flipbook
+        });
+        assert_eq!(
+            effect.validate().unwrap_err().errors,
+            vec![
+                EffectAssetError::InvalidFlipbookSize {
+                    modifier_index: 0,
+                    columns: 4,
+                    rows: 0
+                },
+                EffectAssetError::InvalidFlipbookFps {
+                    modifier_index: 0,
+                    fps: 0.
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn texture_mode() {
+        let effect = test_effect().render(ParticleTextureModifier::default());
+        assert_eq!(
+            effect.render_layout().texture_mode,
+            TextureMode::AlphaFromRed
+        );
+        let effect = test_effect().render(ParticleTextureModifier {
+            mode: TextureMode::RgbaMultiply,
+            ..Default::default()
+        });
+        assert_eq!(
+            effect.render_layout().texture_mode,
+            TextureMode::RgbaMultiply
+        );
+
+        // Effects saved before texture modes existed keep the previous behavior
+        let modifier: Modifier =
+            ron::from_str("ParticleTexture((texture_path: Some(\"cloud.png\")))").unwrap();
+        match modifier {
+            Modifier::ParticleTexture(modifier) => {
+                assert_eq!(modifier.mode, TextureMode::AlphaFromRed)
+            }
+            _ => panic!("expected a particle texture modifier"),
+        }
     }
 }
@@ -21,6 +21,7 @@ use crate::{
         PARTICLES_UPDATE_SHADER_HANDLE,
     },
     spawn::{self, Random},
+    sub_emitter::spawn_sub_emitters,
 };
 
 pub mod draw_graph {
@@ -40,7 +41,8 @@ impl Plugin for HanabiPlugin {
         app.add_asset::<EffectAsset>()
             .insert_resource(Random(spawn::new_rng()))
             .init_resource::<PipelineRegistry>()
-            .init_asset_loader::<EffectAssetLoader>();
+            .init_asset_loader::<EffectAssetLoader>()
+            .add_system(spawn_sub_emitters);
 
         // Register the spawn and update systems
         // app.add_system(hanabi_spawn.system())
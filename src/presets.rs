@@ -0,0 +1,435 @@
+//! Ready-made effects for common use cases.
+//!
+//! Each preset is a function building a complete [`EffectAsset`] from a small parameter struct
+//! controlling its color, scale, and intensity. The returned asset is a regular effect, so it can
+//! be further customized by adding or editing modifiers before being added to the
+//! [`Assets<EffectAsset>`] collection.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_hanabi::{presets::*, *};
+//! fn setup(mut effects: ResMut<Assets<EffectAsset>>) {
+//!     let green_smoke = effects.add(smoke(SmokeParams {
+//!         color: Vec4::new(0.2, 0.6, 0.2, 0.6),
+//!         ..Default::default()
+//!     }));
+//! }
+//! ```
+//!
+//! Like any other effect, the particles are spawned inside the appear areas of the effect
+//! instance, which need to be set at runtime with [`Spawner::set_appear_areas()`]. The initial
+//! speed of the particles is given by the flow speed of those appear areas.
+//!
+//! [`Assets<EffectAsset>`]: bevy::asset::Assets
+
+use bevy::math::{Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccelModifier, ColorOverLifetimeModifier, EffectAsset, Gradient, GradientEnum,
+    PositionSphereModifier, ShapeDimension, SizeOverLifetimeModifier, Spawner,
+};
+
+macro_rules! preset_params {
+    ($(#[$meta:meta])* $name:ident, $color:expr, $scale:expr, $intensity:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+        pub struct $name {
+            /// Base color of the particles, in linear RGBA.
+            pub color: Vec4,
+            /// Overall scale of the effect, applied to the emitter size, the particle size,
+            /// and the acceleration of the particles.
+            pub scale: f32,
+            /// Multiplier applied to the number of particles spawned.
+            pub intensity: f32,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    color: $color,
+                    scale: $scale,
+                    intensity: $intensity,
+                }
+            }
+        }
+    };
+}
+
+preset_params!(
+    /// Parameters of the [`fire()`] preset.
+    FireParams,
+    Vec4::new(1.0, 0.45, 0.1, 1.0),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`smoke()`] preset.
+    SmokeParams,
+    Vec4::new(0.5, 0.5, 0.5, 0.6),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`sparks()`] preset.
+    SparksParams,
+    Vec4::new(1.0, 0.8, 0.3, 1.0),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`rain()`] preset.
+    RainParams,
+    Vec4::new(0.6, 0.7, 1.0, 0.6),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`snow()`] preset.
+    SnowParams,
+    Vec4::new(1.0, 1.0, 1.0, 0.9),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`dust_puff()`] preset.
+    DustPuffParams,
+    Vec4::new(0.6, 0.5, 0.4, 0.5),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`magic_swirl()`] preset.
+    MagicSwirlParams,
+    Vec4::new(0.5, 0.2, 1.0, 1.0),
+    1.0,
+    1.0
+);
+
+preset_params!(
+    /// Parameters of the [`explosion()`] preset.
+    ExplosionParams,
+    Vec4::new(1.0, 0.4, 0.1, 1.0),
+    1.0,
+    1.0
+);
+
+/// Capacity large enough to hold all particles spawned at `rate` and living `lifetime` seconds.
+fn capacity(rate: f32, lifetime: f32) -> u32 {
+    ((rate * lifetime).ceil().max(1.) as u32).next_power_of_two()
+}
+
+fn spawner(mut spawner: Spawner, lifetime: f32) -> Spawner {
+    spawner.set_particles_live_time(lifetime);
+    spawner
+}
+
+fn sphere(radius: f32, dimension: ShapeDimension) -> PositionSphereModifier {
+    PositionSphereModifier {
+        radius,
+        dimension,
+        ..Default::default()
+    }
+}
+
+fn color_over_lifetime(keys: &[(f32, Vec4)]) -> ColorOverLifetimeModifier {
+    let mut gradient = Gradient::new();
+    for &(ratio, color) in keys {
+        gradient.add_key(ratio, color);
+    }
+    ColorOverLifetimeModifier {
+        gradient: GradientEnum::Gradient(gradient),
+    }
+}
+
+fn size_over_lifetime(keys: &[(f32, Vec2)]) -> SizeOverLifetimeModifier {
+    let mut gradient = Gradient::new();
+    for &(ratio, size) in keys {
+        gradient.add_key(ratio, size);
+    }
+    SizeOverLifetimeModifier { gradient }
+}
+
+/// Multiply the RGB components of a color, leaving alpha untouched.
+fn tint(color: Vec4, factor: f32) -> Vec4 {
+    (color.truncate() * factor).extend(color.w)
+}
+
+/// Replace the alpha component of a color.
+fn with_alpha(color: Vec4, alpha: f32) -> Vec4 {
+    color.truncate().extend(alpha)
+}
+
+/// Flames rising from a small volume, fading from a bright core to dark embers.
+pub fn fire(params: FireParams) -> EffectAsset {
+    let FireParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let rate = 200. * intensity;
+    let lifetime = 1.;
+    EffectAsset {
+        name: "fire".to_string(),
+        capacity: capacity(rate, lifetime),
+        spawner: spawner(Spawner::rate(rate.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(0.3 * scale, ShapeDimension::Volume))
+    .update(AccelModifier {
+        accel: Vec3::new(0., 3. * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, tint(color, 2.)),
+        (0.3, color),
+        (1.0, with_alpha(tint(color, 0.2), 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.5 * scale)),
+        (1.0, Vec2::splat(0.1 * scale)),
+    ]))
+}
+
+/// Slowly rising and expanding smoke puffs.
+pub fn smoke(params: SmokeParams) -> EffectAsset {
+    let SmokeParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let rate = 50. * intensity;
+    let lifetime = 4.;
+    EffectAsset {
+        name: "smoke".to_string(),
+        capacity: capacity(rate, lifetime),
+        spawner: spawner(Spawner::rate(rate.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(0.5 * scale, ShapeDimension::Volume))
+    .update(AccelModifier {
+        accel: Vec3::new(0., 0.5 * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, with_alpha(color, 0.)),
+        (0.2, color),
+        (1.0, with_alpha(color, 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.5 * scale)),
+        (1.0, Vec2::splat(2.0 * scale)),
+    ]))
+}
+
+/// Periodic bursts of small bright sparks falling under gravity.
+pub fn sparks(params: SparksParams) -> EffectAsset {
+    let SparksParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let count = 100. * intensity;
+    let lifetime = 0.8;
+    EffectAsset {
+        name: "sparks".to_string(),
+        capacity: capacity(count, 1.),
+        spawner: spawner(Spawner::burst(count.into(), 1.0.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(0.1 * scale, ShapeDimension::Surface))
+    .update(AccelModifier {
+        accel: Vec3::new(0., -9.8 * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, tint(color, 3.)),
+        (0.5, color),
+        (1.0, with_alpha(color, 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.05 * scale)),
+        (1.0, Vec2::splat(0.02 * scale)),
+    ]))
+}
+
+/// Streaks of rain falling from a large volume above the appear areas.
+pub fn rain(params: RainParams) -> EffectAsset {
+    let RainParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let rate = 1000. * intensity;
+    let lifetime = 1.5;
+    EffectAsset {
+        name: "rain".to_string(),
+        capacity: capacity(rate, lifetime),
+        spawner: spawner(Spawner::rate(rate.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(10. * scale, ShapeDimension::Volume))
+    .update(AccelModifier {
+        accel: Vec3::new(0., -20. * scale, 0.),
+    })
+    .render(color_over_lifetime(&[(0.0, color), (1.0, color)]))
+    .render(size_over_lifetime(&[(
+        0.0,
+        Vec2::new(0.02 * scale, 0.3 * scale),
+    )]))
+}
+
+/// Snowflakes gently drifting down from a large volume.
+pub fn snow(params: SnowParams) -> EffectAsset {
+    let SnowParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let rate = 300. * intensity;
+    let lifetime = 8.;
+    EffectAsset {
+        name: "snow".to_string(),
+        capacity: capacity(rate, lifetime),
+        spawner: spawner(Spawner::rate(rate.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(10. * scale, ShapeDimension::Volume))
+    .update(AccelModifier {
+        accel: Vec3::new(0., -0.5 * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, with_alpha(color, 0.)),
+        (0.1, color),
+        (0.9, color),
+        (1.0, with_alpha(color, 0.)),
+    ]))
+    .render(size_over_lifetime(&[(0.0, Vec2::splat(0.1 * scale))]))
+}
+
+/// A single puff of dust expanding and settling down, like after a footstep or an impact.
+pub fn dust_puff(params: DustPuffParams) -> EffectAsset {
+    let DustPuffParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let count = 50. * intensity;
+    let lifetime = 1.5;
+    EffectAsset {
+        name: "dust_puff".to_string(),
+        capacity: capacity(count, 1.),
+        spawner: spawner(Spawner::once(count.into(), true), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(0.3 * scale, ShapeDimension::Volume))
+    .update(AccelModifier {
+        accel: Vec3::new(0., -0.5 * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, color),
+        (1.0, with_alpha(color, 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.2 * scale)),
+        (1.0, Vec2::splat(1.0 * scale)),
+    ]))
+}
+
+/// Glowing motes rising from a sphere, shimmering from white to the effect color.
+pub fn magic_swirl(params: MagicSwirlParams) -> EffectAsset {
+    let MagicSwirlParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let rate = 200. * intensity;
+    let lifetime = 2.;
+    EffectAsset {
+        name: "magic_swirl".to_string(),
+        capacity: capacity(rate, lifetime),
+        spawner: spawner(Spawner::rate(rate.into()), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(1. * scale, ShapeDimension::Surface))
+    .update(AccelModifier {
+        accel: Vec3::new(0., 1. * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, Vec4::ONE),
+        (0.3, tint(color, 2.)),
+        (1.0, with_alpha(color, 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.1 * scale)),
+        (0.5, Vec2::splat(0.15 * scale)),
+        (1.0, Vec2::splat(0.0)),
+    ]))
+}
+
+/// A single blast of particles turning from a white flash into fading smoke.
+pub fn explosion(params: ExplosionParams) -> EffectAsset {
+    let ExplosionParams {
+        color,
+        scale,
+        intensity,
+    } = params;
+    let count = 500. * intensity;
+    let lifetime = 1.2;
+    EffectAsset {
+        name: "explosion".to_string(),
+        capacity: capacity(count, 1.),
+        spawner: spawner(Spawner::once(count.into(), true), lifetime),
+        ..Default::default()
+    }
+    .init(sphere(0.2 * scale, ShapeDimension::Surface))
+    .update(AccelModifier {
+        accel: Vec3::new(0., -2. * scale, 0.),
+    })
+    .render(color_over_lifetime(&[
+        (0.0, Vec4::ONE),
+        (0.1, tint(color, 2.)),
+        (0.4, color),
+        (1.0, Vec4::new(0.1, 0.1, 0.1, 0.)),
+    ]))
+    .render(size_over_lifetime(&[
+        (0.0, Vec2::splat(0.3 * scale)),
+        (1.0, Vec2::splat(1.0 * scale)),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for effect in [
+            fire(Default::default()),
+            smoke(Default::default()),
+            sparks(Default::default()),
+            rain(Default::default()),
+            snow(Default::default()),
+            dust_puff(Default::default()),
+            magic_swirl(Default::default()),
+            explosion(Default::default()),
+        ] {
+            assert!(effect.validate().is_ok(), "{}", effect.name);
+            assert!(!effect.render_layout().lifetime_color_gradients.is_empty());
+            assert!(!effect.render_layout().size_gradients.is_empty());
+        }
+    }
+
+    #[test]
+    fn intensity() {
+        let effect = fire(FireParams {
+            intensity: 2.,
+            ..Default::default()
+        });
+        assert_eq!(effect.capacity, 512);
+        assert_eq!(fire(Default::default()).capacity, 256);
+    }
+}
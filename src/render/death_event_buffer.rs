@@ -0,0 +1,56 @@
+use bevy::render::{
+    render_resource::{Buffer, BufferAddress, BufferDescriptor, BufferUsages},
+    renderer::{RenderDevice, RenderQueue},
+};
+use bytemuck::cast_slice;
+
+/// GPU buffer of the death events of the particles of an effect with a sub-emitter.
+///
+/// The buffer starts with a 16-byte header made of the event count, the number of particles
+/// left to spawn by the sub-emitter, and the number of particles to spawn per event. It's
+/// followed by an array of events, each storing the world-space position of a dead particle.
+/// The header is reset each frame before the update pass; the parent effect appends events
+/// to it, and its sub-emitter consumes them during the same pass.
+pub(crate) struct DeathEventBuffer {
+    /// GPU buffer storing the header and the events.
+    buffer: Buffer,
+    /// Maximum number of events the buffer can hold.
+    capacity: u32,
+}
+
+impl DeathEventBuffer {
+    /// Size in bytes of the buffer header.
+    pub const HEADER_SIZE: u64 = 16;
+
+    /// Size in bytes of a single death event.
+    pub const EVENT_SIZE: u64 = 16;
+
+    /// Create a new buffer able to hold `capacity` death events.
+    pub fn new(render_device: &RenderDevice, capacity: u32, label: Option<&str>) -> Self {
+        let capacity = capacity.max(1);
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label,
+            size: Self::HEADER_SIZE + Self::EVENT_SIZE * capacity as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, capacity }
+    }
+
+    /// Get the underlying GPU buffer.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Maximum number of events the buffer can hold.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Clear all events of the previous frame, and set the number of particles the
+    /// sub-emitter spawns per event.
+    pub fn reset(&self, queue: &RenderQueue, particles_per_event: u32) {
+        let header: [u32; 4] = [0, 0, particles_per_event, 0];
+        queue.write_buffer(&self.buffer, 0, cast_slice(&header[..]));
+    }
+}
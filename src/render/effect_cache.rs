@@ -205,30 +205,61 @@ impl EffectBuffer {
     }
 
     fn pop_free_slice(&mut self, size: u32) -> Option<Range<u32>> {
-        if self.free_slices.is_empty() {
-            return None;
-        }
-        let slice0 = &self.free_slices[0];
-        let mut result = BestRange {
-            range: slice0.clone(),
-            capacity: (slice0.end - slice0.start),
-            index: 0,
-        };
-        for (index, slice) in self.free_slices.iter().skip(1).enumerate() {
+        // Find the smallest free range large enough to hold the requested size
+        let mut result: Option<BestRange> = None;
+        for (index, slice) in self.free_slices.iter().enumerate() {
             let capacity = slice.end - slice.start;
             if size > capacity {
                 continue;
             }
-            if capacity < result.capacity {
-                result = BestRange {
+            if result.as_ref().map_or(true, |best| capacity < best.capacity) {
+                result = Some(BestRange {
                     range: slice.clone(),
                     capacity,
                     index,
-                };
+                });
             }
         }
-        self.free_slices.swap_remove(result.index);
-        Some(result.range)
+        let result = result?;
+        // Only take what's needed, and keep the rest of the range free
+        let range = result.range.start..result.range.start + size;
+        if size < result.capacity {
+            self.free_slices[result.index].start = range.end;
+        } else {
+            self.free_slices.swap_remove(result.index);
+        }
+        Some(range)
+    }
+
+    /// Return a slice previously allocated with [`allocate_particle_slice()`] to the buffer,
+    /// making it available again for future allocations.
+    ///
+    /// [`allocate_particle_slice()`]: EffectBuffer::allocate_particle_slice
+    pub fn free_slice(&mut self, range: Range<u32>) {
+        trace!("EffectBuffer::free_slice: range={:?}", range);
+        // Merge the range with the free ranges right before and after it, if any. Free ranges
+        // are always merged this way, so there's at most one on each side.
+        let mut range = range;
+        if let Some(index) = self
+            .free_slices
+            .iter()
+            .position(|slice| slice.end == range.start)
+        {
+            range.start = self.free_slices.swap_remove(index).start;
+        }
+        if let Some(index) = self
+            .free_slices
+            .iter()
+            .position(|slice| slice.start == range.end)
+        {
+            range.end = self.free_slices.swap_remove(index).end;
+        }
+        if range.end == self.used_size {
+            // Last slice in the buffer; shrink the used size instead.
+            self.used_size = range.start;
+        } else {
+            self.free_slices.push(range);
+        }
     }
 
     // /// Allocate a new slice in the buffer to store the particles of a single effect.
@@ -356,7 +387,7 @@ impl EffectCache {
         capacity: u32,
         item_size: u32,
         //pipeline: ComputePipeline,
-        _queue: &RenderQueue,
+        queue: &RenderQueue,
     ) -> EffectCacheId {
         let (buffer_index, slice) = self
             .effect_buffers
@@ -404,6 +435,16 @@ impl EffectCache {
                 ))
             })
             .unwrap();
+        // A slice reused from a freed one still contains the particles of its previous effect
+        // instance. Clear it, so those particles are all dead for the new instance; a zero
+        // lifetime is always elapsed, and dead particles don't record a death event.
+        let offset = slice.range.start as u64 * slice.item_size as u64;
+        queue.write_buffer(
+            self.effect_buffers[buffer_index].particle_buffer(),
+            offset,
+            &vec![0u8; slice.byte_size()],
+        );
+
         let id = EffectCacheId::new();
         trace!(
             "Insert effect id={:?} buffer_index={} slice={:?}x{}B",
@@ -417,6 +458,23 @@ impl EffectCache {
         id
     }
 
+    /// Remove an effect from the cache, freeing its slice for future allocations.
+    ///
+    /// Return the index of the buffer the effect was allocated from, or `None` if the effect
+    /// was not found in the cache.
+    pub fn remove(&mut self, id: EffectCacheId) -> Option<usize> {
+        let (buffer_index, slice) = self.effect_data_mappings_in_buffer.remove(&id)?;
+        trace!(
+            "Remove effect id={:?} buffer_index={} slice={:?}x{}B",
+            id,
+            buffer_index,
+            slice.range,
+            slice.item_size
+        );
+        self.effect_buffers[buffer_index].free_slice(slice.range);
+        Some(buffer_index)
+    }
+
     pub fn get_slice(&self, id: EffectCacheId) -> EffectSlice {
         self.effect_data_mappings_in_buffer
             .get(&id)
@@ -67,7 +67,7 @@
         (1.0 - not_conformed_to_sphere) * normalize(projected_on_sphere) * length(vVel);
 
     // Euler integration
-    vVel = (vVel + (spawner.accel  + ff_acceleration) * sim_params.dt) 
+    vVel = (vVel + (spawner.accel + modifiers_accel + ff_acceleration) * sim_params.dt) 
         * not_conformed_to_sphere + conformed_field;
 
     // let temp_vPos = vPos;
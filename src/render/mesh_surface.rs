@@ -0,0 +1,202 @@
+use bevy::{
+    math::Vec3,
+    render::{
+        mesh::{Indices, Mesh, PrimitiveTopology, VertexAttributeValues},
+        render_resource::{Buffer, BufferInitDescriptor, BufferUsages},
+        renderer::RenderDevice,
+    },
+};
+use bytemuck::{cast_slice, Pod, Zeroable};
+use std::mem::size_of;
+
+/// A single mesh triangle as stored in a GPU buffer, with its vertex positions and normals.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub(crate) struct MeshTriangle {
+    pub v0: [f32; 3],
+    __pad0: f32,
+    pub v1: [f32; 3],
+    __pad1: f32,
+    pub v2: [f32; 3],
+    __pad2: f32,
+    pub n0: [f32; 3],
+    __pad3: f32,
+    pub n1: [f32; 3],
+    __pad4: f32,
+    pub n2: [f32; 3],
+    __pad5: f32,
+}
+
+impl MeshTriangle {
+    /// Size in bytes of a single triangle in the GPU buffer.
+    pub const SIZE: u64 = size_of::<MeshTriangle>() as u64;
+}
+
+/// CPU copy of the triangles of a mesh used as an emission surface, alongside a table of the
+/// cumulative triangle areas used to sample those triangles proportionally to their area.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct MeshSurfaceData {
+    /// Triangles of the mesh.
+    pub triangles: Vec<MeshTriangle>,
+    /// Sum of the areas of all triangles up to and including the one at the same index.
+    pub cumulative_areas: Vec<f32>,
+}
+
+impl MeshSurfaceData {
+    /// Build the surface data of a mesh.
+    ///
+    /// Returns `None` if the mesh is not a triangle list, has no vertex positions, or has a
+    /// zero surface area. Meshes without vertex normals, and vertices whose normal is zero or not
+    /// finite, use the normal of each triangle.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+            VertexAttributeValues::Float32x3(positions) => positions,
+            _ => return None,
+        };
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) if normals.len() == positions.len() => {
+                Some(normals)
+            }
+            _ => None,
+        };
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as usize).collect(),
+            Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let mut data = MeshSurfaceData::default();
+        let mut total_area = 0.;
+        for face in indices.chunks_exact(3) {
+            let (i0, i1, i2) = (face[0], face[1], face[2]);
+            let v0 = Vec3::from(*positions.get(i0)?);
+            let v1 = Vec3::from(*positions.get(i1)?);
+            let v2 = Vec3::from(*positions.get(i2)?);
+            let cross = (v1 - v0).cross(v2 - v0);
+            let area = cross.length() / 2.;
+            if area <= 0. {
+                // Skip degenerate triangles, which can never be sampled anyway
+                continue;
+            }
+            // Replace the missing or invalid vertex normals with the triangle normal
+            let face_normal = cross.normalize().to_array();
+            let vertex_normal = |i: usize| match normals.map(|normals| Vec3::from(normals[i])) {
+                Some(n) if n.is_finite() && n.length() > 0. => n.to_array(),
+                _ => face_normal,
+            };
+            let (n0, n1, n2) = (vertex_normal(i0), vertex_normal(i1), vertex_normal(i2));
+            total_area += area;
+            data.triangles.push(MeshTriangle {
+                v0: v0.to_array(),
+                v1: v1.to_array(),
+                v2: v2.to_array(),
+                n0,
+                n1,
+                n2,
+                ..Default::default()
+            });
+            data.cumulative_areas.push(total_area);
+        }
+
+        if data.triangles.is_empty() {
+            None
+        } else {
+            Some(data)
+        }
+    }
+}
+
+/// GPU buffers storing the surface data of a mesh, for the update shader to sample.
+pub(crate) struct MeshSurfaceBuffer {
+    /// Buffer of [`MeshTriangle`].
+    triangles: Buffer,
+    /// Buffer of cumulative triangle areas.
+    areas: Buffer,
+}
+
+impl MeshSurfaceBuffer {
+    /// Size in bytes of a single cumulative area in the GPU buffer.
+    pub const AREA_SIZE: u64 = size_of::<f32>() as u64;
+
+    /// Upload the surface data of a mesh to new GPU buffers.
+    pub fn new(render_device: &RenderDevice, data: &MeshSurfaceData) -> Self {
+        let triangles = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("mesh_surface_triangles"),
+            contents: cast_slice(&data.triangles[..]),
+            usage: BufferUsages::STORAGE,
+        });
+        let areas = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("mesh_surface_areas"),
+            contents: cast_slice(&data.cumulative_areas[..]),
+            usage: BufferUsages::STORAGE,
+        });
+        Self { triangles, areas }
+    }
+
+    /// Create a placeholder surface made of a single degenerate triangle, bound for the effects
+    /// not spawning from a mesh.
+    pub fn dummy(render_device: &RenderDevice) -> Self {
+        Self::new(
+            render_device,
+            &MeshSurfaceData {
+                triangles: vec![MeshTriangle::default()],
+                cumulative_areas: vec![0.],
+            },
+        )
+    }
+
+    /// Get the buffer of triangles.
+    pub fn triangles(&self) -> &Buffer {
+        &self.triangles
+    }
+
+    /// Get the buffer of cumulative triangle areas.
+    pub fn areas(&self) -> &Buffer {
+        &self.areas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::shape;
+
+    #[test]
+    fn triangle_size() {
+        assert_eq!(MeshTriangle::SIZE, 96);
+    }
+
+    #[test]
+    fn from_mesh() {
+        let mesh = Mesh::from(shape::Quad::new(bevy::math::Vec2::new(2., 3.)));
+        let data = MeshSurfaceData::from_mesh(&mesh).unwrap();
+        assert_eq!(data.triangles.len(), 2);
+        assert_eq!(data.cumulative_areas, vec![3., 6.]);
+        for triangle in &data.triangles {
+            assert_eq!(triangle.n0, [0., 0., 1.]);
+        }
+
+        // Invalid vertex normals are replaced by the triangle normal
+        let mut mesh = Mesh::from(shape::Quad::new(bevy::math::Vec2::new(2., 3.)));
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            vec![[0., 0., 0.], [f32::NAN, 0., 0.], [0., 1., 0.], [0., 1., 0.]],
+        );
+        let data = MeshSurfaceData::from_mesh(&mesh).unwrap();
+        let normals: Vec<[f32; 3]> = data
+            .triangles
+            .iter()
+            .flat_map(|triangle| [triangle.n0, triangle.n1, triangle.n2])
+            .collect();
+        assert!(normals
+            .iter()
+            .all(|n| *n == [0., 1., 0.] || *n == [0., 0., 1.]));
+        assert!(normals.contains(&[0., 0., 1.]));
+
+        let mesh = Mesh::new(PrimitiveTopology::LineList);
+        assert!(MeshSurfaceData::from_mesh(&mesh).is_none());
+    }
+}
@@ -7,11 +7,12 @@ use bevy::{
         prelude::*,
         system::{lifetimeless::*, SystemState},
     },
-    log::trace,
+    log::{error, trace, warn},
     math::{const_vec3, Mat4, Rect, Vec2, Vec3, Vec4, Vec4Swizzles},
     reflect::TypeUuid,
     render::{
         color::Color,
+        mesh::Mesh,
         render_asset::RenderAssets,
         render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
         render_phase::{Draw, DrawFunctions, RenderPhase, TrackedRenderPass},
@@ -44,21 +45,26 @@ use bevy::core_pipeline::Transparent2d;
 use bevy::core_pipeline::Transparent3d;
 
 use crate::{
-    asset::EffectAsset,
-    modifiers::{ForceFieldParam, FFNUM},
+    asset::{EffectAsset, UpdateLayout},
+    modifiers::{FlipbookMode, FlipbookModifier, ForceFieldParam, TextureMode, FFNUM},
     spawn::{new_rng, Random},
-    Gradient, ParticleEffect, ToWgslString, color_selector::{ColorSelector, ValueRange}, gradient::{GradientEnum, GradientWithColorSelector}, wgsl_syntex_tools::NumberReplace,
+    sub_emitter::SubEmitterOf,
+    gradient::Lerp, Gradient, ParticleEffect, ToWgslString, color_selector::{ColorSelector, ValueRange}, gradient::{GradientEnum, GradientWithColorSelector}, wgsl_syntex_tools::NumberReplace,
 };
 
 mod aligned_buffer_vec;
 mod compute_cache;
+mod death_event_buffer;
 mod effect_cache;
+mod mesh_surface;
 mod pipeline_template;
 pub mod appear_area;
 
 use aligned_buffer_vec::AlignedBufferVec;
 
 pub use compute_cache::{ComputeCache, SpecializedComputePipeline};
+use death_event_buffer::DeathEventBuffer;
+use mesh_surface::{MeshSurfaceBuffer, MeshSurfaceData, MeshTriangle};
 pub use effect_cache::{EffectBuffer, EffectCache, EffectCacheId, EffectSlice};
 pub use pipeline_template::PipelineRegistry;
 
@@ -90,12 +96,81 @@ const DEFAULT_POSITION_CODE: &str = r##"
 "##;
 const DEFAULT_FORCE_FIELD_CODE: &str = r##"
     // the direction is defined inside of wgsl file particles_update.wgsl
-    vVel = vVel + (direction * sim_params.dt);
+    vVel = vVel + (direction + spawner.accel + modifiers_accel) * sim_params.dt;
     vPos = vPos + vVel * sim_params.dt;
 "##;
 
 const FORCE_FIELD_CODE: &str = include_str!("force_field_code.wgsl");
 
+const DEFAULT_LIFETIME_CODE: &str = r##"
+    return spawner.live_time;
+"##;
+
+const TEXTURE_SAMPLE_CODE: &str = r##"
+    var color = textureSample(particle_texture, particle_sampler, in.uv);
+"##;
+
+// Blend the current flipbook frame with the next one
+const TEXTURE_SAMPLE_BLEND_CODE: &str = r##"
+    let color0 = textureSample(particle_texture, particle_sampler, in.uv);
+    let color1 = textureSample(particle_texture, particle_sampler, in.uv_next);
+    var color = mix(color0, color1, in.frame_blend);
+"##;
+
+const DEFAULT_ROTATION_CODE: &str = r##"
+    return vec2<f32>(0., 0.);
+"##;
+
+const DEFAULT_SPAWN_CODE: &str = r##"
+    let remaining = atomicSub(&spawner.spawn, 1) - 1;
+    if (remaining < 0) {
+        return false;
+    }
+    spawn_index = spawner.spawn_offset + u32(remaining);
+    spawn_origin = appear_area_buffer.particleAppearAreas[appear_area_index].position + spawner.origin;
+    return true;
+"##;
+
+// A sub-emitter spawns its particles at the positions where the particles of its parent died,
+// consuming `particles_per_event` spawn slots for each death event recorded by the parent.
+const SUB_EMITTER_SPAWN_CODE: &str = r##"
+    let remaining = atomicSub(&death_events_in.spawn, 1) - 1;
+    if (remaining < 0) {
+        return false;
+    }
+    let particles_per_event = max(death_events_in.particles_per_event, 1);
+    spawn_index = u32(remaining % particles_per_event);
+    let event_index = u32(remaining / particles_per_event);
+    spawn_origin = death_events_in.events[event_index].xyz;
+    return true;
+"##;
+
+// Append a death event to the buffer of the effect instance, and make room for the particles
+// its sub-emitter spawns from it. The update shader calls this exactly once per particle death,
+// either the first update a particle ages past its lifetime, or when an update modifier kills
+// it.
+const DEATH_EVENT_CODE: &str = r##"
+    let event_index = atomicAdd(&death_events_out.count, 1);
+    if (u32(event_index) < arrayLength(&death_events_out.events)) {
+        death_events_out.events[event_index] = vec4<f32>(pos, 1.);
+        atomicAdd(&death_events_out.spawn, death_events_out.particles_per_event);
+    }
+"##;
+
+/// Generate the shader code integrating the forces of the update modifiers and of the force
+/// field of an effect, then applying the constraints of its update modifiers.
+fn update_force_field_code(update_layout: &UpdateLayout) -> String {
+    let force_field_code = if 0.0 == update_layout.force_field[0].force_exponent {
+        DEFAULT_FORCE_FIELD_CODE
+    } else {
+        FORCE_FIELD_CODE
+    };
+    format!(
+        "    var modifiers_accel = vec3<f32>(0., 0., 0.);\n{}\n{}\n{}",
+        update_layout.accel_code, force_field_code, update_layout.post_update_code
+    )
+}
+
 /// Labels for the Hanabi systems.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
 pub enum EffectSystems {
@@ -245,93 +320,150 @@ impl ShaderCode for GradientWithColorSelector<Vec4> {
     }
 }
 
+/// Wrap the code of a render modifier setting `var_name`, so that its value multiplies the value
+/// set by the previous modifiers instead of replacing it.
+fn stack_vertex_modifier(var_name: &str, identity: &str, code: &str) -> String {
+    if code.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{{\nlet prev = {0};\n{0} = {1};\n{2}\n{0} = prev * {0};\n}}\n",
+        var_name, identity, code
+    )
+}
+
 fn correct_wrong_wgsl_syntax(final_code: TokenStream) -> syn::Block {
     let mut expressions = syn::parse2(final_code).unwrap();
     NumberReplace.visit_block_mut(&mut expressions);
     expressions
 }
 
-impl ShaderCode for Gradient<Vec2> {
-    fn to_shader_code(&self) -> String {
-        if self.keys().is_empty() {
-            return String::new();
-        }
-        let mut s: String = self
-            .keys()
-            .iter()
-            .enumerate()
-            .map(|(index, key)| {
+/// Generate the shader code setting `var_name` to the value of a gradient at the current
+/// particle age, relative to its lifetime. The keys of the gradient are declared as constants
+/// named after `key_prefix`.
+fn gradient_shader_code<T: Lerp + ToWgslString>(
+    gradient: &Gradient<T>,
+    var_name: &str,
+    key_prefix: &str,
+) -> String {
+    let keys = gradient.keys();
+    if keys.is_empty() {
+        return String::new();
+    }
+    let mut s: String = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            format!(
+                "let t{0} = {1};\nlet {2}{0} = {3};",
+                index,
+                key.ratio().to_wgsl_string(),
+                key_prefix,
+                key.value.to_wgsl_string()
+            )
+        })
+        .fold("// Gradient\n".into(), |s, key| s + &key + "\n");
+    if keys.len() == 1 {
+        s + &format!("{} = {}0;\n", var_name, key_prefix)
+    } else {
+        s += &format!(
+            "let life = particle.age / particle.lifetime;\nif (life <= t0) {{ {} = {}0; }}\n",
+            var_name, key_prefix
+        );
+        let mut s = (1..keys.len())
+            .map(|index| {
                 format!(
-                    "let t{0} = {1};\nlet v{0} = {2};",
+                    "else if (life <= t{1}) {{ {2} = mix({3}{0}, {3}{1}, (life - t{0}) / (t{1} - t{0})); }}\n",
+                    index - 1,
                     index,
-                    key.ratio().to_wgsl_string(),
-                    key.value.to_wgsl_string()
+                    var_name,
+                    key_prefix
                 )
             })
-            .fold("// Gradient\n".into(), |s, key| s + &key + "\n");
-        if self.keys().len() == 1 {
-            s + "size = v0;\n"
-        } else {
-            // FIXME - particle.age and particle.lifetime are unrelated to Gradient<Vec4>
-            s += "let life = particle.age / particle.lifetime;\nif (life <= t0) { size = v0; }\n";
-            let mut s = self
-                .keys()
-                .iter()
-                .skip(1)
-                .enumerate()
-                .map(|(index, _key)| {
-                    format!(
-                        "else if (life <= t{1}) {{ size = mix(v{0}, v{1}, (life - t{0}) / (t{1} - t{0})); }}\n",
-                        index,
-                        index + 1
-                    )
-                })
-                .fold(s, |s, key| s + &key);
-            s += &format!("else {{ size = v{}; }}\n", self.keys().len() - 1);
-            s
-        }
+            .fold(s, |s, key| s + &key);
+        s += &format!("else {{ {} = {}{}; }}\n", var_name, key_prefix, keys.len() - 1);
+        s
+    }
+}
+
+impl ShaderCode for Gradient<Vec2> {
+    fn to_shader_code(&self) -> String {
+        gradient_shader_code(self, "size", "v")
+    }
+}
+
+impl ShaderCode for Gradient<f32> {
+    fn to_shader_code(&self) -> String {
+        gradient_shader_code(self, "rotation", "r")
     }
 }
 
 impl ShaderCode for Gradient<Vec4> {
     fn to_shader_code(&self) -> String {
-        if self.keys().is_empty() {
-            return String::new();
-        }
-        let mut s: String = self
-            .keys()
-            .iter()
-            .enumerate()
-            .map(|(index, key)| {
-                format!(
-                    "let t{0} = {1};\nlet c{0} = {2};",
-                    index,
-                    key.ratio().to_wgsl_string(),
-                    key.value.to_wgsl_string()
-                )
-            })
-            .fold("// Gradient\n".into(), |s, key| s + &key + "\n");
-        if self.keys().len() == 1 {
-            s + "out.color = c0;\n"
+        gradient_shader_code(self, "out.color", "c")
+    }
+}
+
+impl ShaderCode for FlipbookModifier {
+    fn to_shader_code(&self) -> String {
+        let frame_count = (self.columns.max(1) * self.rows.max(1)) as f32;
+        let (frame_code, wrap_code) = match self.mode {
+            FlipbookMode::OverLifetime => (
+                "clamp(particle.age / particle.lifetime, 0., 1.) * frame_count".to_string(),
+                "min(f, frame_count - 1.)",
+            ),
+            FlipbookMode::Fps { fps } => (
+                format!("particle.age * {}", fps.to_wgsl_string()),
+                "f - floor(f / frame_count) * frame_count",
+            ),
+            FlipbookMode::Random => (
+                "floor(particle.random * frame_count)".to_string(),
+                "min(f, frame_count - 1.)",
+            ),
+        };
+        let blend_code = if self.blend {
+            "\n    out.frame_blend = frame - floor(frame);"
         } else {
-            // FIXME - particle.age and particle.lifetime are unrelated to Gradient<Vec4>
-            s += "let life = particle.age / particle.lifetime;\nif (life <= t0) { out.color = c0; }\n";
-            let mut s = self
-                .keys()
-                .iter()
-                .skip(1)
-                .enumerate()
-                .map(|(index, _key)| {
-                    format!(
-                        "else if (life <= t{1}) {{ out.color = mix(c{0}, c{1}, (life - t{0}) / (t{1} - t{0})); }}\n",
-                        index,
-                        index + 1
-                    )
-                })
-                .fold(s, |s, key| s + &key);
-            s += &format!("else {{ out.color = c{}; }}\n", self.keys().len() - 1);
-            s
+            ""
+        };
+        format!(
+            r##"
+    // Flipbook animation
+    let frame_count = {0};
+    let grid = vec2<f32>({1}, {2});
+    let frame = {3};
+    var f = floor(frame);
+    let frame0 = {4};
+    f = f + 1.;
+    let frame1 = {4};
+    let cell0 = vec2<f32>(frame0 - floor(frame0 / grid.x) * grid.x, floor(frame0 / grid.x));
+    let cell1 = vec2<f32>(frame1 - floor(frame1 / grid.x) * grid.x, floor(frame1 / grid.x));
+    out.uv = (vertex_uv + cell0) / grid;
+    out.uv_next = (vertex_uv + cell1) / grid;{5}
+"##,
+            frame_count.to_wgsl_string(),
+            (self.columns.max(1) as f32).to_wgsl_string(),
+            (self.rows.max(1) as f32).to_wgsl_string(),
+            frame_code,
+            wrap_code,
+            blend_code
+        )
+    }
+}
+
+impl ShaderCode for TextureMode {
+    fn to_shader_code(&self) -> String {
+        match self {
+            TextureMode::RgbaMultiply => "",
+            TextureMode::AlphaFromRed => "    color = vec4<f32>(1.0, 1.0, 1.0, color.r);",
+            TextureMode::AlphaFromLuminance => {
+                "    color = vec4<f32>(1.0, 1.0, 1.0, dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722)));"
+            }
+            TextureMode::Premultiplied => {
+                "    color = vec4<f32>(color.rgb / max(color.a, 0.0001), color.a);"
+            }
         }
+        .to_string()
     }
 }
 
@@ -440,7 +572,10 @@ struct SpawnerParams {
     /// Force field components. One PullingForceFieldParam takes up 32 bytes.
     force_field: [ForceFieldStd430; FFNUM],
     ///
-    __pad0: Vec3,
+    __pad0: Vec2,
+    /// Number of particles spawned by the effect instance before this frame, wrapping around.
+    /// Added to the index of the particles spawned this frame to number them across frames.
+    spawn_offset: u32,
     /// Spawn seed, for randomized modifiers.
     seed: u32,
     ///
@@ -455,6 +590,8 @@ pub struct ParticlesUpdatePipeline {
     spawner_buffer_layout: BindGroupLayout,
     indirect_buffer_layout: BindGroupLayout,
     appear_area_buffer_layout: BindGroupLayout,
+    death_events_layout: BindGroupLayout,
+    mesh_surface_layout: BindGroupLayout,
     pipeline_layout: PipelineLayout,
 }
 
@@ -562,6 +699,43 @@ impl FromWorld for ParticlesUpdatePipeline {
                 }],
             });
 
+        let death_event_buffer_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(
+                    DeathEventBuffer::HEADER_SIZE + DeathEventBuffer::EVENT_SIZE,
+                ),
+            },
+            count: None,
+        };
+        let death_events_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[death_event_buffer_entry(0), death_event_buffer_entry(1)],
+                label: Some("particles_update_death_events_layout"),
+            });
+
+        let mesh_surface_buffer_entry = |binding, min_binding_size| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(min_binding_size),
+            },
+            count: None,
+        };
+        let mesh_surface_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[
+                    mesh_surface_buffer_entry(0, MeshTriangle::SIZE),
+                    mesh_surface_buffer_entry(1, MeshSurfaceBuffer::AREA_SIZE),
+                ],
+                label: Some("particles_update_mesh_surface_layout"),
+            });
+
         let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("particles_update_pipeline_layout"),
             bind_group_layouts: &[
@@ -570,6 +744,8 @@ impl FromWorld for ParticlesUpdatePipeline {
                 &spawner_buffer_layout,
                 &indirect_buffer_layout,
                 &appear_area_buffer_layout,
+                &death_events_layout,
+                &mesh_surface_layout,
             ],
             push_constant_ranges: &[],
         });
@@ -580,6 +756,8 @@ impl FromWorld for ParticlesUpdatePipeline {
             spawner_buffer_layout,
             indirect_buffer_layout,
             appear_area_buffer_layout,
+            death_events_layout,
+            mesh_surface_layout,
             pipeline_layout,
         }
     }
@@ -659,17 +837,41 @@ impl FromWorld for ParticlesRenderPipeline {
 pub struct ParticleUpdatePipelineKey {
     /// Code for the position initialization of newly emitted particles.
     position_code: String,
+    /// Code for the velocity initialization of newly emitted particles, overriding the one set
+    /// by the position code.
+    velocity_code: String,
+    /// Code for the lifetime initialization of newly emitted particles.
+    lifetime_code: String,
+    /// Code for the rotation initialization of newly emitted particles.
+    rotation_code: String,
     force_field_code: String,
+    /// Code recording the death events of particles, for effects with a sub-emitter.
+    death_event_code: String,
+    /// Code deciding whether a dead particle is recycled, and where it spawns.
+    spawn_code: String,
+}
+
+impl ParticleUpdatePipelineKey {
+    /// Assemble the source of the update shader from the template and the code of the key.
+    fn source(&self) -> String {
+        let mut source =
+            PARTICLES_UPDATE_SHADER_TEMPLATE.replace("{{INIT_POS_VEL}}", &self.position_code);
+        source = source.replace("{{INIT_VELOCITY}}", &self.velocity_code);
+        source = source.replace("{{INIT_LIFETIME}}", &self.lifetime_code);
+        source = source.replace("{{INIT_ROTATION}}", &self.rotation_code);
+
+        source = source.replace("{{FORCE_FIELD_CODE}}", &self.force_field_code);
+        source = source.replace("{{DEATH_EVENT_CODE}}", &self.death_event_code);
+        source = source.replace("{{SPAWN_CODE}}", &self.spawn_code);
+        source
+    }
 }
 
 impl SpecializedComputePipeline for ParticlesUpdatePipeline {
     type Key = ParticleUpdatePipelineKey;
 
     fn specialize(&self, key: Self::Key, render_device: &RenderDevice) -> ComputePipeline {
-        let mut source =
-            PARTICLES_UPDATE_SHADER_TEMPLATE.replace("{{INIT_POS_VEL}}", &key.position_code);
-
-        source = source.replace("{{FORCE_FIELD_CODE}}", &key.force_field_code);
+        let source = key.source();
 
         //trace!("Specialized compute pipeline:\n{}", source);
 
@@ -855,6 +1057,8 @@ pub struct ExtractedEffect {
     /// Number of particles to spawn this frame for the effect.
     /// Obtained from calling [`Spawner::tick()`] on the source effect instance.
     pub spawn_count: u32,
+    /// Number of particles spawned by the effect instance before this frame, wrapping around.
+    pub spawn_offset: u32,
     /// Global transform of the effect origin.
     pub transform: Mat4,
     /// Constant acceleration applied to all particles.
@@ -877,8 +1081,26 @@ pub struct ExtractedEffect {
     pub shader: Handle<Shader>,
     /// Update position code.
     pub position_code: String,
+    /// Initial velocity code.
+    pub velocity_code: String,
+    /// Initial lifetime code.
+    pub lifetime_code: String,
+    /// Initial rotation code.
+    pub rotation_code: String,
     /// Update force field code.
     pub force_field_code: String,
+    /// Death event recording code.
+    pub death_event_code: String,
+    /// Particle spawning code.
+    pub spawn_code: String,
+    /// Number of particles the sub-emitter of this effect spawns per death event, if any.
+    pub death_events_out: Option<u32>,
+    /// Entity of the parent effect instance, if this effect is a sub-emitter.
+    pub death_events_in: Option<Entity>,
+    /// Nesting depth of the sub-emitter, or zero for a regular effect.
+    pub sub_emitter_depth: u32,
+    /// Mesh to spawn the particles on, if any.
+    pub mesh_surface: Option<HandleId>,
 
     /// update the areas for particles to present
     pub appear_areas: Vec<AppearAreaInfo>,
@@ -906,6 +1128,188 @@ pub struct ExtractedEffects {
     pub removed_effect_entities: Vec<Entity>,
     /// Newly added effects without a GPU allocation yet.
     pub added_effects: Vec<AddedEffect>,
+    /// Existing effects whose asset was loaded or modified this frame, and which may need a new
+    /// GPU allocation if the effect capacity changed.
+    pub reloaded_effects: Vec<AddedEffect>,
+    /// Surface data of the meshes effects spawn their particles on, indexed by mesh handle.
+    mesh_surfaces: HashMap<HandleId, MeshSurfaceData>,
+    /// Meshes whose surface data was extracted this frame, and needs to be uploaded to GPU.
+    updated_mesh_surfaces: Vec<HandleId>,
+    /// Meshes particles can't spawn on, already reported as such.
+    invalid_mesh_surfaces: HashSet<HandleId>,
+}
+
+/// Data derived from the modifiers of an effect asset, shared by all its instances.
+///
+/// Deriving the layouts of an effect and generating its shader code is too costly to be done
+/// for each instance each frame, so [`extract_effects()`] caches this per effect asset until
+/// the asset is modified.
+pub(crate) struct EffectCode {
+    /// Constant acceleration applied to all particles.
+    accel: Vec3,
+    /// Force field applied to all particles in the "update" phase.
+    force_field: [ForceFieldParam; FFNUM],
+    /// Mesh to spawn the particles on, if any.
+    mesh_surface: Option<HandleId>,
+    /// Whether the particles are textured.
+    has_image: bool,
+    /// Texture to modulate the particle color.
+    image_handle_id: HandleId,
+    /// Render shader.
+    shader: Handle<Shader>,
+    /// Update position code.
+    position_code: String,
+    /// Initial velocity code.
+    velocity_code: String,
+    /// Initial lifetime code.
+    lifetime_code: String,
+    /// Initial rotation code.
+    rotation_code: String,
+    /// Update force field code.
+    force_field_code: String,
+    /// Number of particles the sub-emitter of the effect spawns per death event, if any.
+    death_events_out: Option<u32>,
+}
+
+impl EffectCode {
+    /// Derive the layouts of an effect asset, and generate its shader code.
+    fn new(
+        asset: &EffectAsset,
+        pipeline_registry: &mut PipelineRegistry,
+        shaders: &mut Assets<Shader>,
+    ) -> Self {
+        let init_layout = asset.init_layout();
+        let update_layout = asset.update_layout();
+        let render_layout = asset.render_layout();
+
+        // Extract the acceleration
+        let accel = update_layout.accel;
+        let force_field = update_layout.force_field;
+
+        // Generate the shader code for the position initializing of newly emitted particles
+        let position_code = &init_layout.position_code;
+        let position_code = if position_code.is_empty() {
+            DEFAULT_POSITION_CODE.to_owned()
+        } else {
+            position_code.clone()
+        };
+        // Velocity modifiers replace the velocity set by the position modifiers
+        let velocity_code = if init_layout.velocity_code.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "    ret.vel = vec3<f32>(0., 0., 0.);\n{}",
+                init_layout.velocity_code
+            )
+        };
+        let lifetime_code = if init_layout.lifetime_code.is_empty() {
+            DEFAULT_LIFETIME_CODE.to_owned()
+        } else {
+            init_layout.lifetime_code.clone()
+        };
+        let rotation_code = if init_layout.rotation_code.is_empty() {
+            DEFAULT_ROTATION_CODE.to_owned()
+        } else {
+            init_layout.rotation_code.clone()
+        };
+
+        // Generate the shader code for the force field of newly emitted particles
+        let force_field_code = update_force_field_code(&update_layout);
+
+        // Number of particles spawned per death event by the sub-emitter of the effect, if any
+        let death_events_out = update_layout
+            .sub_emitter
+            .as_ref()
+            .map(|(_, particles_per_event)| *particles_per_event);
+
+        // Generate the shader code for the size and color gradients, each multiplying the
+        // result of the previous ones.
+        let mut vertex_modifiers = String::new();
+        for grad in &render_layout.size_gradients {
+            vertex_modifiers +=
+                &stack_vertex_modifier("size", "vec2<f32>(1., 1.)", &grad.to_shader_code());
+        }
+        for grad in &render_layout.rotation_gradients {
+            // Rotations add up instead of multiplying
+            let code = grad.to_shader_code();
+            if !code.is_empty() {
+                vertex_modifiers += &format!(
+                    "{{\nlet prev = rotation;\nrotation = 0.;\n{}\nrotation = prev + rotation;\n}}\n",
+                    code
+                );
+            }
+        }
+        for grad in &render_layout.lifetime_color_gradients {
+            let code = match grad {
+                GradientEnum::ColorSelector(color_selector) => color_selector.to_shader_code(),
+                GradientEnum::Gradient(gradient) => gradient.to_shader_code(),
+            };
+            vertex_modifiers +=
+                &stack_vertex_modifier("out.color", "vec4<f32>(1., 1., 1., 1.)", &code);
+        }
+        if let Some(tint) = render_layout.color_tint {
+            vertex_modifiers += &format!("out.color = out.color * {};\n", tint.to_wgsl_string());
+        }
+
+        trace!("vertex_modifiers={}", vertex_modifiers);
+
+        // Generate the shader code orienting the particle quads
+        let mut orientation_code = render_layout.billboard.to_orientation_code();
+        orientation_code += &match render_layout.orient_along_velocity {
+            Some(stretch) => format!(
+                r##"
+    // Align along the velocity, and rotate around it to face the camera
+    let particle_speed = length(particle.vel);
+    let side = cross(view.world_position - particle.pos, particle.vel);
+    if (particle_speed > 0. && length(side) > 0.) {{
+        axis_x = particle.vel / particle_speed * (1. + {} * particle_speed);
+        axis_y = normalize(side);
+    }}
+"##,
+                stretch.to_wgsl_string()
+            ),
+            None => String::new(),
+        };
+
+        // Generate the shader code animating the texture as a flipbook, and sampling it
+        let flipbook_code = render_layout
+            .flipbook
+            .map_or(String::new(), |flipbook| flipbook.to_shader_code());
+        let texture_sample_code = if render_layout.flipbook.map_or(false, |f| f.blend) {
+            TEXTURE_SAMPLE_BLEND_CODE
+        } else {
+            TEXTURE_SAMPLE_CODE
+        };
+
+        // Configure the shader template, and make sure a corresponding shader asset exists
+        let shader_source = PARTICLES_RENDER_SHADER_TEMPLATE
+            .replace("{{VERTEX_MODIFIERS}}", &vertex_modifiers)
+            .replace("{{ORIENTATION_CODE}}", &orientation_code)
+            .replace("{{FLIPBOOK_CODE}}", &flipbook_code)
+            .replace("{{TEXTURE_SAMPLE_CODE}}", texture_sample_code)
+            .replace(
+                "{{TEXTURE_MODE_CODE}}",
+                &render_layout.texture_mode.to_shader_code(),
+            );
+        let shader = pipeline_registry.configure(&shader_source, shaders);
+
+        EffectCode {
+            accel,
+            force_field,
+            mesh_surface: init_layout.mesh_surface.map(|handle| handle.id),
+            has_image: render_layout.particle_texture.is_some(),
+            image_handle_id: render_layout
+                .particle_texture
+                .map_or(HandleId::default::<Image>(), |handle| handle.id),
+            shader,
+            position_code,
+            velocity_code,
+            lifetime_code,
+            rotation_code,
+            force_field_code,
+            death_events_out,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -950,9 +1354,12 @@ pub(crate) fn extract_effects(
     time: Res<Time>,
     effects: Res<Assets<EffectAsset>>,
     _images: Res<Assets<Image>>,
+    meshes: Res<Assets<Mesh>>,
     mut shaders: ResMut<Assets<Shader>>,
     mut pipeline_registry: ResMut<PipelineRegistry>,
     mut rng: ResMut<Random>,
+    mut effect_code: Local<HashMap<HandleId, EffectCode>>,
+    mut effect_validity: Local<HashMap<HandleId, bool>>,
     mut query: ParamSet<(
         // All existing ParticleEffect components
         Query<(
@@ -960,6 +1367,7 @@ pub(crate) fn extract_effects(
             &ComputedVisibility,
             &mut ParticleEffect, //TODO - Split EffectAsset::Spawner (desc) and ParticleEffect::SpawnerData (runtime data), and init the latter on component add without a need for the former
             &GlobalTransform,
+            Option<&SubEmitterOf>,
         )>,
         // Newly added ParticleEffect components
         Query<
@@ -972,38 +1380,115 @@ pub(crate) fn extract_effects(
         >,
     )>,
     removed_effects: RemovedComponents<ParticleEffect>,
+    mut effect_events: EventReader<AssetEvent<EffectAsset>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
 ) {
     trace!("extract_effects");
 
+    // Collect the effect assets loaded, modified, or removed since last frame. The shader code of
+    // an effect is cached until one of those events, so discard it to generate it again below
+    // from the new asset, and refresh the per-instance data of the reloaded effects.
+    let mut reloaded_assets: HashSet<Handle<EffectAsset>> = HashSet::default();
+    for event in effect_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                effect_code.remove(&handle.id);
+                effect_validity.remove(&handle.id);
+                reloaded_assets.insert(handle.clone_weak());
+            }
+            AssetEvent::Removed { handle } => {
+                effect_code.remove(&handle.id);
+                effect_validity.remove(&handle.id);
+            }
+        }
+    }
+
     // Save simulation params into render world
     let mut sim_params = render_world.get_resource_mut::<SimParams>().unwrap();
     let dt = time.delta_seconds();
     sim_params.time = time.seconds_since_startup();
     sim_params.dt = dt;
 
+    // The particles of an effect are allocated in a single storage buffer binding
+    let max_binding_size = render_world
+        .get_resource::<RenderDevice>()
+        .unwrap()
+        .limits()
+        .max_storage_buffer_binding_size;
+
     let mut extracted_effects = render_world.get_resource_mut::<ExtractedEffects>().unwrap();
 
+    // Discard the surface data of the meshes modified or removed since last frame; it's extracted
+    // again below if still in use.
+    extracted_effects.updated_mesh_surfaces.clear();
+    for event in mesh_events.iter() {
+        match event {
+            AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => {
+                extracted_effects.mesh_surfaces.remove(&handle.id);
+                extracted_effects.invalid_mesh_surfaces.remove(&handle.id);
+            }
+            AssetEvent::Created { .. } => {}
+        }
+    }
+
     // Collect removed effects for later GPU data purge
     extracted_effects.removed_effect_entities = removed_effects.iter().collect();
+    for entity in removed_effects.iter() {
+        extracted_effects.effects.remove(&entity);
+    }
 
-    // Collect added effects for later GPU data allocation
+    // Collect added effects for later GPU data allocation. Effects whose asset is not loaded yet
+    // are allocated once the asset becomes available, as part of the reloaded effects below.
     extracted_effects.added_effects = query
         .p1()
         .iter()
-        .map(|(entity, effect)| {
+        .filter_map(|(entity, effect)| {
             let handle = effect.handle.clone_weak();
-            let asset = effects.get(&effect.handle).unwrap();
-            AddedEffect {
+            let asset = effects.get(&effect.handle)?;
+            if !is_valid_effect(&mut effect_validity, &handle, asset, max_binding_size) {
+                return None;
+            }
+            Some(AddedEffect {
                 entity,
                 capacity: asset.capacity,
-                item_size: Particle::std430_size_static() as u32, // effect.item_size(),
+                item_size: particle_item_size(), // effect.item_size(),
                 handle,
-            }
+            })
         })
         .collect();
 
+    // Collect existing effects whose asset was (re)loaded, and reset their spawner from the
+    // new asset content.
+    extracted_effects.reloaded_effects.clear();
+    if !reloaded_assets.is_empty() {
+        for (entity, _, mut effect, _, _) in query.p0().iter_mut() {
+            if !reloaded_assets.contains(&effect.handle) {
+                continue;
+            }
+            if let Some(asset) = effects.get(&effect.handle) {
+                // Drop the allocation of the instances of an effect made invalid, until fixed
+                if !is_valid_effect(&mut effect_validity, &effect.handle, asset, max_binding_size)
+                {
+                    extracted_effects.removed_effect_entities.push(entity);
+                    extracted_effects.effects.remove(&entity);
+                    continue;
+                }
+                trace!("Reloading effect {:?} on entity {:?}", effect.handle, entity);
+                effect.reload_spawner(&asset.spawner);
+                extracted_effects.reloaded_effects.push(AddedEffect {
+                    entity,
+                    capacity: asset.capacity,
+                    item_size: particle_item_size(),
+                    handle: effect.handle.clone_weak(),
+                });
+            }
+        }
+    }
+
     // Loop over all existing effects to update them
-    for (entity, computed_visibility, mut effect, transform) in query.p0().iter_mut() {
+    for (entity, computed_visibility, mut effect, transform, sub_emitter_of) in
+        query.p0().iter_mut()
+    {
         // Check if visible
         if !computed_visibility.is_visible {
             continue;
@@ -1011,71 +1496,92 @@ pub(crate) fn extract_effects(
 
         // Check if asset is available, otherwise silently ignore
         if let Some(asset) = effects.get(&effect.handle) {
+            if !is_valid_effect(&mut effect_validity, &effect.handle, asset, max_binding_size) {
+                continue;
+            }
+
             //let size = image.texture_descriptor.size;
 
             // Tick the effect's spawner to determine the spawn count for this frame
             let spawner = effect.spawner(&asset.spawner);
 
+            // A sub-emitter spawns its particles where the ones of its parent die instead of
+            // inside its appear areas, but still needs one for the update pass to run.
+            if sub_emitter_of.is_some() && spawner.get_appear_areas().is_empty() {
+                spawner.set_appear_areas(vec![AppearAreaInfo::new(Vec3::ZERO, Vec3::ZERO)]);
+            }
+
             let appear_areas = spawner.get_appear_areas();
             let particle_live_time = spawner.get_particles_live_time();
             let spawn_count = spawner.tick(dt, &mut rng.0) * (appear_areas.len() as u32);
-
-            // Extract the acceleration
-            let accel = asset.update_layout.accel;
-            let force_field = asset.update_layout.force_field;
-
-            // Generate the shader code for the position initializing of newly emitted particles
-            // TODO - Move that to a pre-pass, not each frame!
-            let position_code = &asset.init_layout.position_code;
-            let position_code = if position_code.is_empty() {
-                DEFAULT_POSITION_CODE.to_owned()
-            } else {
-                position_code.clone()
+            let spawn_offset = effect.spawn_offset;
+            effect.spawn_offset = spawn_offset.wrapping_add(spawn_count);
+
+            // Derive the layouts and the shader code from the modifiers of the effect, once for
+            // all the instances of the effect until its asset is modified.
+            let code = effect_code
+                .entry(effect.handle.id)
+                .or_insert_with(|| EffectCode::new(asset, &mut pipeline_registry, &mut shaders));
+
+            // Extract the surface data of the mesh to spawn the particles on, if not already
+            // done. Skip the effect until the mesh is available, or if it can't be used.
+            let mesh_surface = match code.mesh_surface {
+                Some(handle) => {
+                    if !extracted_effects.mesh_surfaces.contains_key(&handle) {
+                        if extracted_effects.invalid_mesh_surfaces.contains(&handle) {
+                            continue;
+                        }
+                        let mesh = match meshes.get(handle) {
+                            Some(mesh) => mesh,
+                            None => continue,
+                        };
+                        let data = match MeshSurfaceData::from_mesh(mesh) {
+                            Some(data) => data,
+                            None => {
+                                warn!(
+                                    "Cannot spawn particles of effect {:?} on mesh {:?}: the mesh \
+                                    must be a triangle list with positions and a non-zero area.",
+                                    effect.handle, handle
+                                );
+                                extracted_effects.invalid_mesh_surfaces.insert(handle);
+                                continue;
+                            }
+                        };
+                        trace!(
+                            "Extracted mesh surface {:?}: {} triangles",
+                            handle,
+                            data.triangles.len()
+                        );
+                        extracted_effects.mesh_surfaces.insert(handle, data);
+                        extracted_effects.updated_mesh_surfaces.push(handle);
+                    }
+                    Some(handle)
+                }
+                None => None,
             };
 
-            // Generate the shader code for the force field of newly emitted particles
-            // TODO - Move that to a pre-pass, not each frame!
-            // let force_field_code = &asset.init_layout.force_field_code;
-            // let force_field_code = if force_field_code.is_empty() {
-            let force_field_code = if 0.0 == asset.update_layout.force_field[0].force_exponent {
-                DEFAULT_FORCE_FIELD_CODE.to_owned()
+            // Generate the shader code recording the particle deaths for the sub-emitter of the
+            // effect, if any, and the code spawning the particles of a sub-emitter from them.
+            let death_event_code = if code.death_events_out.is_some() {
+                DEATH_EVENT_CODE.to_owned()
             } else {
-                FORCE_FIELD_CODE.to_owned()
+                String::new()
             };
-
-            let mut vertex_modifiers= String::new();
-            if let Some(grad) = &asset.render_layout.size_color_gradient {
-                vertex_modifiers = grad.to_shader_code();
+            let death_events_in = sub_emitter_of.map(|sub_emitter_of| sub_emitter_of.parent);
+            let spawn_code = if death_events_in.is_some() {
+                SUB_EMITTER_SPAWN_CODE.to_owned()
+            } else {
+                DEFAULT_SPAWN_CODE.to_owned()
             };
-            // Generate the shader code for the color over lifetime gradient.
-            // TODO - Move that to a pre-pass, not each frame!
-             match &asset.render_layout.lifetime_color_gradient {
-                    Some(GradientEnum::ColorSelector(color_selector))=>
-                    vertex_modifiers += &color_selector.to_shader_code(),
-                    Some(GradientEnum::Gradient(gradient))=>
-                    vertex_modifiers +=&gradient.to_shader_code(),
-                    _ => (),
-                };
-
-            
-            trace!("vertex_modifiers={}", vertex_modifiers);
-
-            // Configure the shader template, and make sure a corresponding shader asset exists
-            let shader_source =
-                PARTICLES_RENDER_SHADER_TEMPLATE.replace("{{VERTEX_MODIFIERS}}", &vertex_modifiers);
-            let shader = pipeline_registry.configure(&shader_source, &mut shaders);
+            let sub_emitter_depth = sub_emitter_of.map_or(0, |sub_emitter_of| sub_emitter_of.depth);
 
             trace!(
                 "extracted: handle={:?} shader={:?} has_image={} position_code={} force_field_code={}",
                 effect.handle,
-                shader,
-                if asset.render_layout.particle_texture.is_some() {
-                    "Y"
-                } else {
-                    "N"
-                },
-                position_code,
-                force_field_code,
+                code.shader,
+                if code.has_image { "Y" } else { "N" },
+                code.position_code,
+                code.force_field_code,
             );
 
             extracted_effects.effects.insert(
@@ -1083,9 +1589,10 @@ pub(crate) fn extract_effects(
                 ExtractedEffect {
                     handle: effect.handle.clone_weak(),
                     spawn_count,
+                    spawn_offset,
                     transform: transform.compute_matrix(),
-                    accel, //effect.color,
-                    force_field,
+                    accel: code.accel, //effect.color,
+                    force_field: code.force_field,
                     color: Color::RED,
                     rect: Rect {
                         left: -0.1,
@@ -1095,21 +1602,71 @@ pub(crate) fn extract_effects(
                                      //.custom_size
                                      //.unwrap_or_else(|| Vec2::new(size.width as f32, size.height as f32)),
                     },
-                    has_image: asset.render_layout.particle_texture.is_some(),
-                    image_handle_id: asset
-                        .render_layout
-                        .particle_texture
-                        .clone()
-                        .map_or(HandleId::default::<Image>(), |handle| handle.id),
-                    shader,
-                    position_code,
-                    force_field_code,
+                    has_image: code.has_image,
+                    image_handle_id: code.image_handle_id,
+                    shader: code.shader.clone(),
+                    position_code: code.position_code.clone(),
+                    velocity_code: code.velocity_code.clone(),
+                    lifetime_code: code.lifetime_code.clone(),
+                    rotation_code: code.rotation_code.clone(),
+                    force_field_code: code.force_field_code.clone(),
+                    death_event_code,
+                    spawn_code,
+                    death_events_out: code.death_events_out,
+                    death_events_in,
+                    sub_emitter_depth,
+                    mesh_surface,
                     appear_areas,
                     particle_live_time,
                 },
             );
         }
     }
+
+    // Drop the code of the effects without any instance, and the surface data of the meshes no
+    // effect instance spawns its particles on anymore. Prepare then drops their GPU buffer too.
+    let used_effects: HashSet<HandleId> = query
+        .p0()
+        .iter()
+        .map(|(_, _, effect, _, _)| effect.handle.id)
+        .collect();
+    effect_code.retain(|id, _| used_effects.contains(id));
+    effect_validity.retain(|id, _| used_effects.contains(id));
+    let used_meshes: HashSet<HandleId> = used_effects
+        .into_iter()
+        .filter_map(|id| match effect_code.get(&id) {
+            Some(code) => code.mesh_surface,
+            // Only hidden so far, so no code generated yet
+            None => Some(effects.get(id)?.init_layout().mesh_surface?.id),
+        })
+        .collect();
+    extracted_effects
+        .mesh_surfaces
+        .retain(|id, _| used_meshes.contains(id));
+    extracted_effects
+        .invalid_mesh_surfaces
+        .retain(|id| used_meshes.contains(id));
+}
+
+/// Check whether the particles of an effect can be allocated on the render device.
+///
+/// The result is cached until the asset of the effect changes, so the problems of an invalid
+/// effect are only logged once instead of every frame.
+fn is_valid_effect(
+    effect_validity: &mut HashMap<HandleId, bool>,
+    handle: &Handle<EffectAsset>,
+    asset: &EffectAsset,
+    max_binding_size: u32,
+) -> bool {
+    *effect_validity.entry(handle.id).or_insert_with(|| {
+        match asset.validate_for_device(max_binding_size) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Skipping effect {:?}. {}", handle, err);
+                false
+            }
+        }
+    })
 }
 
 /// A single particle as stored in a GPU buffer.
@@ -1123,7 +1680,20 @@ struct Particle {
     /// Particle velocity in effect space (local or world).
     pub velocity: [f32; 3],
     /// Total particle lifetime.
-    pub lifetime: f32, 
+    pub lifetime: f32,
+    /// Particle rotation in the plane of its quad, in radians.
+    pub rotation: f32,
+    /// Particle angular velocity, in radians per second.
+    pub angular_velocity: f32,
+    /// Random value in \[0:1\] drawn when the particle is emitted.
+    pub random: f32,
+    /// Padding to align the particles on 16 bytes.
+    __pad: f32,
+}
+
+/// Size in bytes of a single particle in the GPU particle buffer.
+pub(crate) fn particle_item_size() -> u32 {
+    Particle::std430_size_static() as u32
 }
 /// A single vertex of a particle mesh as stored in a GPU buffer.
 #[repr(C)]
@@ -1141,9 +1711,9 @@ struct ParticleVertex {
 /// for the current frame, for all views in the frame, and consumed by [`queue_effects()`]
 /// to actually enqueue the drawning commands to draw those effects.
 pub(crate) struct EffectsMeta {
-    /// Map from an entity with a [`ParticleEffect`] component attached to it, to the associated
-    /// effect slice allocated in an [`EffectCache`].
-    entity_map: HashMap<Entity, EffectSlice>,
+    /// Map from an entity with a [`ParticleEffect`] component attached to it, to the identifier
+    /// of the associated effect slice allocated in an [`EffectCache`].
+    entity_map: HashMap<Entity, EffectCacheId>,
     /// Global effect cache for all effects in use.
     effect_cache: EffectCache,
     /// Bind group for the camera view, containing the camera projection and other uniform
@@ -1169,6 +1739,20 @@ pub(crate) struct EffectsMeta {
     /// Bind group for the ParticleApearArea buffer itself.
     appear_area_buffer_bind_group: Option<BindGroup>,
     appear_areas_buffer_list: HashMap<u32, BufferVec<ParticleAppearArea>>,
+    /// Death event buffers of the effect instances with a sub-emitter, indexed by the entity of
+    /// the instance. Each instance records the death events of its particles in its own buffer,
+    /// which only the sub-emitter instance spawned as its child consumes.
+    death_event_buffers: HashMap<Entity, DeathEventBuffer>,
+    /// Placeholder buffer bound in place of the death event output of effects without a
+    /// sub-emitter.
+    dummy_death_events_out: DeathEventBuffer,
+    /// Placeholder buffer bound in place of the death event input of effects which are not a
+    /// sub-emitter.
+    dummy_death_events_in: DeathEventBuffer,
+    /// GPU surface data of the meshes effects spawn their particles on, indexed by mesh handle.
+    mesh_surface_buffers: HashMap<HandleId, MeshSurfaceBuffer>,
+    /// Placeholder surface bound for the effects not spawning their particles on a mesh.
+    dummy_mesh_surface: MeshSurfaceBuffer,
 }
 
 impl EffectsMeta {
@@ -1189,6 +1773,12 @@ impl EffectsMeta {
         // let appear_areas_buffer = BufferVec::new(BufferUsages::STORAGE);
         let mesh_boxes_buffer = BufferVec::new(BufferUsages::STORAGE);
 
+        let dummy_death_events_out =
+            DeathEventBuffer::new(&device, 1, Some("dummy_death_events_out"));
+        let dummy_death_events_in =
+            DeathEventBuffer::new(&device, 1, Some("dummy_death_events_in"));
+        let dummy_mesh_surface = MeshSurfaceBuffer::dummy(&device);
+
         Self {
             entity_map: HashMap::default(),
             effect_cache: EffectCache::new(device),
@@ -1207,6 +1797,11 @@ impl EffectsMeta {
             appear_area_buffer_bind_group: None,
             appear_areas_buffer_list: HashMap::default(),
             mesh_boxes_buffer,
+            death_event_buffers: HashMap::default(),
+            dummy_death_events_out,
+            dummy_death_events_in,
+            mesh_surface_buffers: HashMap::default(),
+            dummy_mesh_surface,
         }
     }
 }
@@ -1255,8 +1850,30 @@ pub struct EffectBatch {
     shader: Handle<Shader>,
     /// Update position code.
     position_code: String,
+    /// Initial velocity code.
+    velocity_code: String,
+    /// Initial lifetime code.
+    lifetime_code: String,
+    /// Initial rotation code.
+    rotation_code: String,
     /// Update force field code.
     force_field_code: String,
+    /// Death event recording code.
+    death_event_code: String,
+    /// Particle spawning code.
+    spawn_code: String,
+    /// Entity of the effect instance whose death event buffer this batch writes to, if the
+    /// effect has a sub-emitter.
+    death_events_out: Option<Entity>,
+    /// Entity of the parent effect instance whose death event buffer this batch consumes, if
+    /// the effect is a sub-emitter.
+    death_events_in: Option<Entity>,
+    /// Nesting depth of the sub-emitter, or zero for a regular effect. Batches are updated by
+    /// increasing depth, so that parents record their death events before the sub-emitters
+    /// consume them.
+    sub_emitter_depth: u32,
+    /// Handle of the mesh the effects of this batch spawn their particles on, if any.
+    mesh_surface: Option<HandleId>,
     /// Compute pipeline specialized for this batch.
     compute_pipeline: Option<ComputePipeline>,
     /// appear area base index in buffer
@@ -1324,17 +1941,59 @@ pub(crate) fn prepare_effects(
             //update_pipeline.pipeline.clone(),
             &render_queue,
         );
-        let slice = effects_meta.effect_cache.get_slice(id);
-        effects_meta.entity_map.insert(entity, slice);
+        effects_meta.entity_map.insert(entity, id);
     }
 
-    // Deallocate GPU data for destroyed effect instances. This will automatically drop any group where
-    // there is no more effect slice.
-    for _entity in extracted_effects.removed_effect_entities.iter() {
-        unimplemented!("Remove particle effect.");
-        //effects_meta.remove(&*entity);
+    // Reallocate GPU data for effect instances whose asset was reloaded, if the new asset doesn't
+    // fit the existing allocation anymore. This also allocates effects added before their asset
+    // finished loading.
+    for reloaded_effect in extracted_effects.reloaded_effects.drain(..) {
+        let entity = reloaded_effect.entity;
+        if let Some(id) = effects_meta.entity_map.get(&entity).copied() {
+            let slice = effects_meta.effect_cache.get_slice(id);
+            if slice.slice.end - slice.slice.start == reloaded_effect.capacity
+                && slice.item_size == reloaded_effect.item_size
+            {
+                continue;
+            }
+            trace!(
+                "Reallocating effect on entity {:?}: capacity {} -> {}",
+                entity,
+                slice.slice.end - slice.slice.start,
+                reloaded_effect.capacity
+            );
+            effects_meta.effect_cache.remove(id);
+        }
+        let id = effects_meta.effect_cache.insert(
+            reloaded_effect.handle,
+            reloaded_effect.capacity,
+            reloaded_effect.item_size,
+            &render_queue,
+        );
+        effects_meta.entity_map.insert(entity, id);
+    }
+
+    // Deallocate GPU data for destroyed effect instances, returning their slice to the free list
+    // of their effect buffer.
+    for entity in extracted_effects.removed_effect_entities.drain(..) {
+        if let Some(id) = effects_meta.entity_map.remove(&entity) {
+            effects_meta.effect_cache.remove(id);
+        }
     }
 
+    // Upload the surface data of the meshes extracted this frame, and drop the ones not in use
+    // anymore.
+    let updated_mesh_surfaces = std::mem::take(&mut extracted_effects.updated_mesh_surfaces);
+    for id in updated_mesh_surfaces {
+        if let Some(data) = extracted_effects.mesh_surfaces.get(&id) {
+            let buffer = MeshSurfaceBuffer::new(&render_device, data);
+            effects_meta.mesh_surface_buffers.insert(id, buffer);
+        }
+    }
+    effects_meta
+        .mesh_surface_buffers
+        .retain(|id, _| extracted_effects.mesh_surfaces.contains_key(id));
+
     // // sort first by z and then by handle. this ensures that, when possible, batches span multiple z layers
     // // batches won't span z-layers if there is another batch between them
     // extracted_effects.effects.sort_by(|a, b| {
@@ -1348,9 +2007,10 @@ pub(crate) fn prepare_effects(
     let mut effect_entity_list = extracted_effects
         .effects
         .iter()
-        .map(|(entity, extracted_effect)| {
-            let slice = effects_meta.entity_map.get(entity).unwrap().clone();
-            (slice, extracted_effect)
+        .filter_map(|(entity, extracted_effect)| {
+            let id = *effects_meta.entity_map.get(entity)?;
+            let slice = effects_meta.effect_cache.get_slice(id);
+            Some((slice, *entity, extracted_effect))
         })
         .collect::<Vec<_>>();
     trace!("Collected {} extracted effects", effect_entity_list.len());
@@ -1358,6 +2018,33 @@ pub(crate) fn prepare_effects(
     // Sort first by effect buffer, then by slice range (see EffectSlice)
     effect_entity_list.sort_by(|a, b| a.0.cmp(&b.0));
 
+    // Allocate or reset the death event buffers of the effect instances with a sub-emitter. Each
+    // instance owns its buffer, so that its sub-emitter instance only consumes the death events
+    // of its own particles. Each particle dies at most once per frame, so a buffer needs at most
+    // one event per particle of the instance.
+    let mut death_event_capacities: HashMap<Entity, (u32, u32)> = HashMap::default();
+    for (slice, entity, extracted_effect) in &effect_entity_list {
+        if let Some(particles_per_event) = extracted_effect.death_events_out {
+            let capacity = slice.slice.end - slice.slice.start;
+            death_event_capacities.insert(*entity, (capacity, particles_per_event));
+        }
+    }
+    effects_meta
+        .death_event_buffers
+        .retain(|id, _| death_event_capacities.contains_key(id));
+    for (id, (capacity, particles_per_event)) in death_event_capacities {
+        let needs_alloc = effects_meta
+            .death_event_buffers
+            .get(&id)
+            .map_or(true, |buffer| buffer.capacity() < capacity);
+        if needs_alloc {
+            trace!("Allocating death event buffer for entity {:?}: capacity {}", id, capacity);
+            let buffer = DeathEventBuffer::new(&render_device, capacity, Some("death_event_buffer"));
+            effects_meta.death_event_buffers.insert(id, buffer);
+        }
+        effects_meta.death_event_buffers[&id].reset(&render_queue, particles_per_event);
+    }
+
     // Loop on all extracted effects in order
     effects_meta.spawner_buffer.clear();
     // effects_meta.appear_areas_buffer_list.clear();
@@ -1372,13 +2059,22 @@ pub(crate) fn prepare_effects(
     let mut end = 0;
     let mut num_emitted = 0;
     let mut position_code = String::default();
+    let mut velocity_code = String::default();
+    let mut lifetime_code = String::default();
+    let mut rotation_code = String::default();
     let mut force_field_code = String::default();
+    let mut death_event_code = String::default();
+    let mut spawn_code = String::default();
+    let mut death_events_out = None;
+    let mut death_events_in = None;
+    let mut sub_emitter_depth = 0;
+    let mut mesh_surface = None;
     let mut appear_areas_item_count = 0u32;
     let mut appear_areas_offset_base = 0u32;
     let mut actived = false;
     //  let appear_area_buffer_list = effects_meta
     //                 .appear_areas_buffer_list;
-    for (slice, extracted_effect) in effect_entity_list {
+    for (slice, entity, extracted_effect) in effect_entity_list {
         let buffer_index = slice.group_index;
         let range = slice.slice;
 
@@ -1429,7 +2125,16 @@ pub(crate) fn prepare_effects(
                         image_handle_id,
                         shader: shader.clone(),
                         position_code: position_code.clone(),
+                        velocity_code: velocity_code.clone(),
+                        lifetime_code: lifetime_code.clone(),
+                        rotation_code: rotation_code.clone(),
                         force_field_code: force_field_code.clone(),
+                        death_event_code: death_event_code.clone(),
+                        spawn_code: spawn_code.clone(),
+                        death_events_out,
+                        death_events_in,
+                        sub_emitter_depth,
+                        mesh_surface,
                         compute_pipeline: None,
                         appear_areas_offset_base,
                         appear_areas_item_count,
@@ -1461,9 +2166,23 @@ pub(crate) fn prepare_effects(
         position_code = extracted_effect.position_code.clone();
         trace!("position_code = {}", position_code);
 
+        velocity_code = extracted_effect.velocity_code.clone();
+        trace!("velocity_code = {}", velocity_code);
+
+        lifetime_code = extracted_effect.lifetime_code.clone();
+        trace!("lifetime_code = {}", lifetime_code);
+
+        rotation_code = extracted_effect.rotation_code.clone();
+        trace!("rotation_code = {}", rotation_code);
+
         force_field_code = extracted_effect.force_field_code.clone();
         trace!("force_field_code = {}", force_field_code);
 
+        death_event_code = extracted_effect.death_event_code.clone();
+        spawn_code = extracted_effect.spawn_code.clone();
+        sub_emitter_depth = extracted_effect.sub_emitter_depth;
+        mesh_surface = extracted_effect.mesh_surface;
+
         // extract the force field and turn it into a struct that is compliant with Std430,
         // namely ForceFieldStd430
         let mut extracted_force_field = [ForceFieldStd430::default(); FFNUM];
@@ -1492,6 +2211,7 @@ pub(crate) fn prepare_effects(
         let spawner_params = SpawnerParams {
             spawn: extracted_effect.spawn_count as i32,
             count: 0,
+            spawn_offset: extracted_effect.spawn_offset,
             origin: extracted_effect.transform.col(3).truncate(),
             accel: extracted_effect.accel,
             force_field: extracted_force_field, // extracted_effect.force_field,
@@ -1526,8 +2246,17 @@ pub(crate) fn prepare_effects(
                     .push(item.to_particle_appear_area());
             });
         }
+        // Each instance with a sub-emitter, and each sub-emitter instance, binds its own death
+        // event buffer, so can't share a batch with other instances.
+        let instance_death_events_out = extracted_effect.death_events_out.map(|_| entity);
+        let instance_death_events_in = extracted_effect.death_events_in;
+        let has_death_events = instance_death_events_out.is_some()
+            || instance_death_events_in.is_some()
+            || death_events_out.is_some()
+            || death_events_in.is_some();
+
         trace!("slice = {}-{} | prev end = {}", range.start, range.end, end);
-        if (range.start > end) || (item_size != slice.item_size) {
+        if (range.start > end) || (item_size != slice.item_size) || has_death_events {
             // Discontinuous slices; create a new batch
             if end > start {
                 // Record the previous batch
@@ -1553,7 +2282,16 @@ pub(crate) fn prepare_effects(
                     image_handle_id,
                     shader: shader.clone(),
                     position_code: position_code.clone(),
+                    velocity_code: velocity_code.clone(),
+                    lifetime_code: lifetime_code.clone(),
+                    rotation_code: rotation_code.clone(),
                     force_field_code: force_field_code.clone(),
+                    death_event_code: death_event_code.clone(),
+                    spawn_code: spawn_code.clone(),
+                    death_events_out,
+                    death_events_in,
+                    sub_emitter_depth,
+                    mesh_surface,
                     compute_pipeline: None,
                     appear_areas_offset_base,
                     appear_areas_item_count,
@@ -1565,6 +2303,8 @@ pub(crate) fn prepare_effects(
             item_size = slice.item_size;
         }
         end = range.end;
+        death_events_out = instance_death_events_out;
+        death_events_in = instance_death_events_in;
 
         effects_meta
             .appear_areas_buffer_list
@@ -1597,7 +2337,16 @@ pub(crate) fn prepare_effects(
             image_handle_id,
             shader,
             position_code,
+            velocity_code,
+            lifetime_code,
+            rotation_code,
             force_field_code,
+            death_event_code,
+            spawn_code,
+            death_events_out,
+            death_events_in,
+            sub_emitter_depth,
+            mesh_surface,
             compute_pipeline: None,
             appear_areas_offset_base,
             appear_areas_item_count,
@@ -1636,6 +2385,12 @@ pub struct EffectBindGroups {
 
     ///
     update_appear_area_buffers: HashMap<u32, BindGroup>,
+    /// Bind groups for the death event buffers written and read by a batch (update stage),
+    /// indexed by the entities of the effect instances owning those buffers.
+    update_death_events: HashMap<(Option<Entity>, Option<Entity>), BindGroup>,
+    /// Bind groups for the mesh surface buffers read by a batch (update stage), indexed by the
+    /// handle of the mesh.
+    update_mesh_surfaces: HashMap<Option<HandleId>, BindGroup>,
 
     ///
     images: HashMap<Handle<Image>, BindGroup>,
@@ -1866,6 +2621,10 @@ pub(crate) fn queue_effects(
 
     // Queue the update
     // TODO - Move to prepare(), there's no view-dependent thing here!+
+    // The death event and mesh surface buffers may be reallocated each frame, so rebuild their
+    // bind groups.
+    effect_bind_groups.update_death_events.clear();
+    effect_bind_groups.update_mesh_surfaces.clear();
     for (_entity, mut batch) in effect_batches.iter_mut() {
         // Specialize the update pipeline based on the effect batch
         trace!(
@@ -1919,12 +2678,77 @@ pub(crate) fn queue_effects(
             &update_pipeline,
             ParticleUpdatePipelineKey {
                 position_code: batch.position_code.clone(),
+                velocity_code: batch.velocity_code.clone(),
+                lifetime_code: batch.lifetime_code.clone(),
+                rotation_code: batch.rotation_code.clone(),
                 force_field_code: batch.force_field_code.clone(),
+                death_event_code: batch.death_event_code.clone(),
+                spawn_code: batch.spawn_code.clone(),
             },
             &render_device,
         );
         trace!("Update pipeline specialized: {:?}", compute_pipeline);
 
+        // Bind the death event buffers of the batch, falling back to the placeholder ones when
+        // the effect has no sub-emitter or is not a sub-emitter itself, or if the parent of a
+        // sub-emitter has no instance this frame.
+        let key = (batch.death_events_out, batch.death_events_in);
+        if !effect_bind_groups.update_death_events.contains_key(&key) {
+            let death_events_out = batch
+                .death_events_out
+                .and_then(|id| effects_meta.death_event_buffers.get(&id))
+                .unwrap_or(&effects_meta.dummy_death_events_out);
+            let death_events_in = batch
+                .death_events_in
+                .filter(|id| batch.death_events_out != Some(*id))
+                .and_then(|id| effects_meta.death_event_buffers.get(&id))
+                .unwrap_or(&effects_meta.dummy_death_events_in);
+            let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: death_events_out.buffer().as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: death_events_in.buffer().as_entire_binding(),
+                    },
+                ],
+                label: Some("vfx_death_events_bind_group_update"),
+                layout: &update_pipeline.death_events_layout,
+            });
+            effect_bind_groups.update_death_events.insert(key, bind_group);
+        }
+
+        // Bind the mesh surface of the batch, falling back to the placeholder one for effects
+        // not spawning their particles on a mesh.
+        if !effect_bind_groups
+            .update_mesh_surfaces
+            .contains_key(&batch.mesh_surface)
+        {
+            let mesh_surface = batch
+                .mesh_surface
+                .and_then(|id| effects_meta.mesh_surface_buffers.get(&id))
+                .unwrap_or(&effects_meta.dummy_mesh_surface);
+            let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: mesh_surface.triangles().as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: mesh_surface.areas().as_entire_binding(),
+                    },
+                ],
+                label: Some("vfx_mesh_surface_bind_group_update"),
+                layout: &update_pipeline.mesh_surface_layout,
+            });
+            effect_bind_groups
+                .update_mesh_surfaces
+                .insert(batch.mesh_surface, bind_group);
+        }
+
         batch.compute_pipeline = Some(compute_pipeline.clone());
     }
 
@@ -2392,7 +3216,12 @@ impl Node for ParticleUpdateNode {
                 trace!("loop over effect batches...");
                 //for effect_entity in extracted_effect_entities.entities.iter().copied() {
 
-                for batch in self.effect_query.iter_manual(world) {
+                // Update parent effects before their sub-emitters, so the death events recorded
+                // this frame are consumed during the same frame.
+                let mut batches = self.effect_query.iter_manual(world).collect::<Vec<_>>();
+                batches.sort_by_key(|batch| batch.sub_emitter_depth);
+
+                for batch in batches {
                     if let Some(compute_pipeline) = &batch.compute_pipeline {
                         //for (effect_entity, effect_slice) in effects_meta.entity_map.iter() {
                         // Retrieve the ExtractedEffect from the entity
@@ -2469,6 +3298,16 @@ impl Node for ParticleUpdateNode {
                             &appear_area_bind_group,
                             &[appear_area_base],
                         );
+                        let death_events_bind_group = effect_bind_groups
+                            .update_death_events
+                            .get(&(batch.death_events_out, batch.death_events_in))
+                            .unwrap();
+                        compute_pass.set_bind_group(5, death_events_bind_group, &[]);
+                        let mesh_surface_bind_group = effect_bind_groups
+                            .update_mesh_surfaces
+                            .get(&batch.mesh_surface)
+                            .unwrap();
+                        compute_pass.set_bind_group(6, mesh_surface_bind_group, &[]);
                         compute_pass.dispatch(workgroup_count, 1, 1);
                         trace!("compute dispatched");
                     }
@@ -2487,14 +3326,138 @@ impl Node for ParticleUpdateNode {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        CollisionModifier, CollisionResponse, CurlNoiseModifier, DragModifier, KillVolumeModifier,
+        SdfShape, SpeedLimitModifier, SubEmitterModifier, VortexModifier,
+    };
     use bevy::math::Vec4;
 
+    /// Assemble the update shader of a regular effect instance, the way the extraction does.
+    fn update_shader_source(effect: &EffectAsset) -> String {
+        let update_layout = effect.update_layout();
+        let death_event_code = if update_layout.sub_emitter.is_some() {
+            DEATH_EVENT_CODE.to_owned()
+        } else {
+            String::new()
+        };
+        ParticleUpdatePipelineKey {
+            position_code: DEFAULT_POSITION_CODE.to_owned(),
+            velocity_code: String::new(),
+            lifetime_code: DEFAULT_LIFETIME_CODE.to_owned(),
+            rotation_code: DEFAULT_ROTATION_CODE.to_owned(),
+            force_field_code: update_force_field_code(&update_layout),
+            death_event_code,
+            spawn_code: DEFAULT_SPAWN_CODE.to_owned(),
+        }
+        .source()
+    }
+
     #[test]
     fn layout_flags() {
         let flags = LayoutFlags::default();
         assert_eq!(flags, LayoutFlags::NONE);
     }
 
+    #[test]
+    fn particle_size() {
+        // Must match the stride of the particle buffer in the shaders
+        assert_eq!(particle_item_size(), 48);
+    }
+
+    #[test]
+    fn flipbook_shader_code() {
+        let flipbook = FlipbookModifier {
+            columns: 4,
+            rows: 2,
+            ..Default::default()
+        };
+        let code = flipbook.to_shader_code();
+        assert!(code.contains("let frame_count = 8.;"));
+        assert!(code.contains("let grid = vec2<f32>(4., 2.);"));
+        assert!(!code.contains("out.frame_blend"));
+
+        let flipbook = FlipbookModifier {
+            mode: FlipbookMode::Fps { fps: 12. },
+            blend: true,
+            ..flipbook
+        };
+        let code = flipbook.to_shader_code();
+        assert!(code.contains("particle.age * 12."));
+        assert!(code.contains("out.frame_blend"));
+    }
+
+    #[test]
+    fn kill_volume_sub_emitter() {
+        let effect = EffectAsset::default()
+            .update(KillVolumeModifier::default())
+            .update(SubEmitterModifier {
+                particles_per_event: 4,
+                ..Default::default()
+            });
+        let source = update_shader_source(&effect);
+
+        // The particles killed by the volume record their death event before being written
+        // back, in full, as dead.
+        let kill = source.find("vAge = vLifetime;").unwrap();
+        let kill_block = &source[kill..source.rfind("// Increment alive particle count").unwrap()];
+        assert!(kill_block.contains("record_death_event(vPos);"));
+        assert!(kill_block.contains(".lifetime = vLifetime;"));
+        assert!(kill_block.contains(".angular_velocity = vAngularVelocity;"));
+        assert!(source.contains("atomicAdd(&death_events_out.spawn"));
+
+        assert!(ProcessedShader::Wgsl(Cow::Owned(source)).reflect().is_ok());
+    }
+
+    #[test]
+    fn update_modifiers_shader() {
+        let effect = EffectAsset::default()
+            .update(DragModifier {
+                linear: 0.5,
+                quadratic: 0.1,
+            })
+            .update(CurlNoiseModifier::default())
+            .update(VortexModifier::default())
+            .update(CollisionModifier {
+                shapes: vec![
+                    SdfShape::Plane {
+                        point: Vec3::ZERO,
+                        normal: Vec3::Y,
+                    },
+                    SdfShape::Sphere {
+                        center: Vec3::ZERO,
+                        radius: 2.,
+                    },
+                    SdfShape::Box {
+                        center: Vec3::ZERO,
+                        half_extents: Vec3::ONE,
+                    },
+                ],
+                ..Default::default()
+            })
+            .update(CollisionModifier {
+                shapes: vec![SdfShape::Sphere {
+                    center: Vec3::X,
+                    radius: 1.,
+                }],
+                response: CollisionResponse::Kill,
+            })
+            .update(KillVolumeModifier::default())
+            .update(SpeedLimitModifier {
+                max_speed: 10.,
+                max_axis_speed: Some(Vec3::splat(5.)),
+                ..Default::default()
+            })
+            .update(SubEmitterModifier {
+                particles_per_event: 2,
+                ..Default::default()
+            });
+
+        // Parse and validate the entire update shader, so any typo in the code generated by the
+        // update modifiers fails here instead of at runtime.
+        let source = update_shader_source(&effect);
+        ProcessedShader::Wgsl(Cow::Owned(source)).reflect().unwrap();
+    }
+
     #[test]
     fn to_shader_code() {
         let mut grad = Gradient::new();
@@ -2520,5 +3483,315 @@ else { out.color = c1; }
 "#,
             grad.to_shader_code()
         );
+
+        // Other gradient types only differ by the variable they set
+        let mut grad = Gradient::new();
+        grad.add_key(0.0, Vec2::splat(1.0));
+        assert!(grad.to_shader_code().ends_with("\nsize = v0;\n"));
+        let mut grad = Gradient::<f32>::new();
+        grad.add_key(0.0, 0.0);
+        grad.add_key(1.0, 3.0);
+        let code = grad.to_shader_code();
+        assert!(code.contains("let r1 = 3.;"));
+        assert!(code.contains("{ rotation = mix(r0, r1, (life - t0) / (t1 - t0)); }"));
+    }
+}
+
+#[cfg(all(test, feature = "gpu_tests"))]
+mod gpu_tests {
+    use super::*;
+    use crate::{
+        test_utils::MockRenderer, CurlNoiseModifier, DragModifier, SdfShape, SpeedLimitModifier,
+        VortexModifier,
+    };
+    use bevy::render::render_resource::{
+        BufferInitDescriptor, CommandEncoderDescriptor, ComputePassDescriptor,
+    };
+
+    /// Minimal compute shader running some update code on a single particle.
+    const PARTICLE_CODE_SHADER: &str = r##"
+struct SimParams {
+    dt: f32;
+    time: f32;
+};
+
+struct TestParticle {
+    pos: vec3<f32>;
+    age: f32;
+    vel: vec3<f32>;
+    lifetime: f32;
+    accel: vec3<f32>;
+    dt: f32;
+};
+
+{{FUNCTIONS}}
+
+[[group(0), binding(0)]] var<storage, read_write> particle : TestParticle;
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    let sim_params = SimParams(particle.dt, 0.);
+    var vPos = particle.pos;
+    var vVel = particle.vel;
+    var vAge = particle.age;
+    var vLifetime = particle.lifetime;
+    var modifiers_accel = vec3<f32>(0., 0., 0.);
+{{CODE}}
+    particle.pos = vPos;
+    particle.vel = vVel;
+    particle.age = vAge;
+    particle.accel = modifiers_accel;
+}
+"##;
+
+    /// Particle read back from [`run_particle_code()`].
+    struct TestParticle {
+        age: f32,
+        vel: Vec3,
+        accel: Vec3,
+    }
+
+    /// Noise functions of the update shader, used by the code of [`CurlNoiseModifier`].
+    fn noise_functions() -> String {
+        let section = |start: &str, end: &str| {
+            let start = PARTICLES_UPDATE_SHADER_TEMPLATE.find(start).unwrap();
+            let end = start + PARTICLES_UPDATE_SHADER_TEMPLATE[start..].find(end).unwrap();
+            &PARTICLES_UPDATE_SHADER_TEMPLATE[start..end]
+        };
+        section("fn pcg_hash(", "fn to_float01(").to_owned()
+            + section("// Hash of a point of the integer lattice", "// Pick a random mesh")
+    }
+
+    /// Run some update code on the GPU on a single particle at `pos` moving at `vel`.
+    fn run_particle_code(code: &str, pos: Vec3, vel: Vec3, dt: f32) -> TestParticle {
+        let renderer = MockRenderer::new();
+        let device = renderer.device();
+        let queue = renderer.queue();
+
+        let source = PARTICLE_CODE_SHADER
+            .replace("{{FUNCTIONS}}", &noise_functions())
+            .replace("{{CODE}}", code);
+        let module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("particle_code_test"),
+            source: ShaderSource::Wgsl(Cow::Owned(source)),
+        });
+        let pipeline = device.create_compute_pipeline(&RawComputePipelineDescriptor {
+            label: Some("particle_code_test"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+
+        let data: [f32; 12] = [
+            pos.x, pos.y, pos.z, 0., vel.x, vel.y, vel.z, 1., 0., 0., 0., dt,
+        ];
+        let size = (data.len() * 4) as u64;
+        let buffer = device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("particle_code_test"),
+            contents: cast_slice(&data),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: Some("particle_code_test_readback"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("particle_code_test"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("particle_code_test"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("particle_code_test"),
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&buffer, 0, &readback, 0, size);
+        queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        device.map_buffer(&slice, MapMode::Read);
+        let view = slice.get_mapped_range();
+        let data: &[f32] = cast_slice(&view);
+        TestParticle {
+            age: data[3],
+            vel: Vec3::new(data[4], data[5], data[6]),
+            accel: Vec3::new(data[8], data[9], data[10]),
+        }
+    }
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn drag() {
+        let effect = EffectAsset::default().update(DragModifier {
+            linear: 0.5,
+            quadratic: 0.25,
+        });
+        let code = effect.update_layout().accel_code;
+
+        // Drag coefficient of 0.5 + 0.25 x 2
+        let particle = run_particle_code(&code, Vec3::ZERO, Vec3::new(2., 0., 0.), 0.1);
+        assert_vec3_eq(particle.accel, Vec3::new(-2., 0., 0.));
+
+        // A strong drag is clamped so that it stops the particle in one step, without reversing
+        // its velocity.
+        let effect = EffectAsset::default().update(DragModifier {
+            linear: 100.,
+            quadratic: 0.,
+        });
+        let code = effect.update_layout().accel_code;
+        let particle = run_particle_code(&code, Vec3::ZERO, Vec3::new(2., 0., 0.), 0.1);
+        assert_vec3_eq(particle.accel, Vec3::new(-20., 0., 0.));
+    }
+
+    #[test]
+    fn speed_limit() {
+        let effect = EffectAsset::default().update(SpeedLimitModifier {
+            min_speed: 1.,
+            max_speed: 5.,
+            max_axis_speed: None,
+        });
+        let code = effect.update_layout().post_update_code;
+        let run = |vel| run_particle_code(&code, Vec3::ZERO, vel, 0.1).vel;
+        assert_vec3_eq(run(Vec3::new(0., 10., 0.)), Vec3::new(0., 5., 0.));
+        assert_vec3_eq(run(Vec3::new(0.5, 0., 0.)), Vec3::new(1., 0., 0.));
+        assert_vec3_eq(run(Vec3::new(0., 0., 3.)), Vec3::new(0., 0., 3.));
+        // Particles at rest stay at rest
+        assert_vec3_eq(run(Vec3::ZERO), Vec3::ZERO);
+
+        let effect = EffectAsset::default().update(SpeedLimitModifier {
+            max_axis_speed: Some(Vec3::new(1., -2., 3.)),
+            ..Default::default()
+        });
+        let code = effect.update_layout().post_update_code;
+        let vel = run_particle_code(&code, Vec3::ZERO, Vec3::new(-4., 4., 2.), 0.1).vel;
+        assert_vec3_eq(vel, Vec3::new(-1., 2., 2.));
+    }
+
+    #[test]
+    fn curl_noise() {
+        // Output the noise as velocity, its divergence as age, and the magnitude of each of the
+        // partial derivatives making up the divergence as acceleration.
+        let code = r##"
+    let e = 0.02;
+    let dx = vec3<f32>(e, 0., 0.);
+    let dy = vec3<f32>(0., e, 0.);
+    let dz = vec3<f32>(0., 0., e);
+    let ddx = (curl_noise(vPos + dx, 7u).x - curl_noise(vPos - dx, 7u).x) / (2. * e);
+    let ddy = (curl_noise(vPos + dy, 7u).y - curl_noise(vPos - dy, 7u).y) / (2. * e);
+    let ddz = (curl_noise(vPos + dz, 7u).z - curl_noise(vPos - dz, 7u).z) / (2. * e);
+    vVel = curl_noise(vPos, 7u);
+    vAge = ddx + ddy + ddz;
+    modifiers_accel = abs(vec3<f32>(ddx, ddy, ddz));
+"##;
+        for pos in [
+            Vec3::new(0.5, 1.25, -2.75),
+            Vec3::new(3.3, -7.1, 0.2),
+            Vec3::new(10.4, 20.7, -5.9),
+            Vec3::new(-0.3, -0.6, 0.9),
+        ] {
+            let particle = run_particle_code(code, pos, Vec3::ZERO, 0.1);
+            assert!(particle.vel.is_finite() && particle.vel.length() > 0.);
+            // The field is divergence-free up to the error of the finite differences, so the
+            // partial derivatives cancel out.
+            let magnitude = particle.accel.x + particle.accel.y + particle.accel.z;
+            assert!(
+                particle.age.abs() < 0.1 * magnitude,
+                "divergence {} at {:?}, partial derivatives {:?}",
+                particle.age,
+                pos,
+                particle.accel
+            );
+        }
+
+        // Several octaves, on large coordinates
+        let effect = EffectAsset::default().update(CurlNoiseModifier {
+            frequency: 3.,
+            octaves: CurlNoiseModifier::MAX_OCTAVES,
+            ..Default::default()
+        });
+        let code = effect.update_layout().accel_code;
+        let particle = run_particle_code(&code, Vec3::new(1e4, -2e4, 5e3), Vec3::ZERO, 0.1);
+        assert!(particle.accel.is_finite());
+    }
+
+    #[test]
+    fn vortex() {
+        let effect = EffectAsset::default().update(VortexModifier {
+            speed: 2.,
+            stiffness: 4.,
+            ..Default::default()
+        });
+        let code = effect.update_layout().accel_code;
+
+        // A particle at rest on the X axis accelerates toward the orbital speed around the Y
+        // axis, at the rate of the stiffness.
+        let particle = run_particle_code(&code, Vec3::X, Vec3::ZERO, 0.1);
+        assert_vec3_eq(particle.accel, Vec3::new(0., 0., -8.));
+
+        // A particle already orbiting at the target speed only gets the centripetal acceleration
+        let particle = run_particle_code(&code, Vec3::X, Vec3::new(0., 0., -2.), 0.1);
+        assert_vec3_eq(particle.accel, Vec3::new(-4., 0., 0.));
+
+        // The response is limited to reaching the target speed in a single step
+        let particle = run_particle_code(&code, Vec3::X, Vec3::ZERO, 0.5);
+        assert_vec3_eq(particle.accel, Vec3::new(0., 0., -4.));
+    }
+
+    #[test]
+    fn sdf_normals() {
+        // Output the distance as age and the normal as velocity
+        let run = |shape: SdfShape, pos| {
+            let code = format!("    {}\n    vAge = d;\n    vVel = n;", shape.to_sdf_code());
+            let particle = run_particle_code(&code, pos, Vec3::ZERO, 0.1);
+            (particle.age, particle.vel)
+        };
+        let assert_sdf = |shape, pos, d: f32, n| {
+            let (age, vel) = run(shape, pos);
+            assert!((age - d).abs() < 1e-5, "{} != {}", age, d);
+            assert_vec3_eq(vel, n);
+        };
+
+        let plane = SdfShape::Plane {
+            point: Vec3::Y,
+            normal: Vec3::new(0., 2., 0.),
+        };
+        assert_sdf(plane, Vec3::new(3., 4., 0.), 3., Vec3::Y);
+        assert_sdf(plane, Vec3::new(3., -1., 0.), -2., Vec3::Y);
+
+        let sphere = SdfShape::Sphere {
+            center: Vec3::X,
+            radius: 2.,
+        };
+        assert_sdf(sphere, Vec3::new(1., 0., 5.), 3., Vec3::Z);
+        assert_sdf(sphere, Vec3::new(0., 0., 0.), -1., -Vec3::X);
+        // Arbitrary but valid normal at the center
+        assert_sdf(sphere, Vec3::X, -2., Vec3::Y);
+
+        let cube = SdfShape::Box {
+            center: Vec3::ZERO,
+            half_extents: Vec3::new(1., 2., 3.),
+        };
+        // Outside, facing a face or a corner
+        assert_sdf(cube, Vec3::new(3., 0., 0.), 2., Vec3::X);
+        let corner_normal = Vec3::new(1., -1., 0.).normalize();
+        assert_sdf(cube, Vec3::new(2., -3., 0.), 2f32.sqrt(), corner_normal);
+        // Inside, the normal of the closest face
+        assert_sdf(cube, Vec3::new(0., 1.5, 0.), -0.5, Vec3::Y);
+        assert_sdf(cube, Vec3::new(0.2, 0., -2.9), -0.1, -Vec3::Z);
     }
 }
@@ -1,5 +1,13 @@
 struct View {
     view_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    inverse_view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    world_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    width: f32;
+    height: f32;
 };
 
 struct Particle {
@@ -7,10 +15,14 @@ struct Particle {
     age: f32;
     vel: vec3<f32>;
     lifetime: f32;
+    rotation: f32;
+    angular_velocity: f32;
+    random: f32;
+    __pad: f32;
 };
 
 struct ParticlesBuffer {
-    particles: [[stride(32)]] array<Particle>;
+    particles: [[stride(48)]] array<Particle>;
 };
 
 struct VertexOutput {
@@ -18,6 +30,9 @@ struct VertexOutput {
     [[location(0)]] color: vec4<f32>;
 #ifdef PARTICLE_TEXTURE
     [[location(1)]] uv: vec2<f32>;
+    // Flipbook frame following the one of `uv`, and blending factor between them
+    [[location(2)]] uv_next: vec2<f32>;
+    [[location(3)]] frame_blend: f32;
 #endif
 };
 
@@ -63,15 +78,34 @@ fn vertex(
     var out: VertexOutput;
 #ifdef PARTICLE_TEXTURE
     out.uv = vertex_uv;
+    out.uv_next = vertex_uv;
+    out.frame_blend = 0.0;
+{{FLIPBOOK_CODE}}
 #endif
 
     var size = vec2<f32>(1.0, 1.0);
+    var rotation = particle.rotation;
+    out.color = vec4<f32>(1.0, 1.0, 1.0, 1.0);
 
 {{VERTEX_MODIFIERS}}
 
+    // Orient the particle quad
+    var axis_x = vec3<f32>(1.0, 0.0, 0.0);
+    var axis_y = vec3<f32>(0.0, 1.0, 0.0);
+
+{{ORIENTATION_CODE}}
+
+    // Rotate the particle quad in its plane
+    let cos_rotation = cos(rotation);
+    let sin_rotation = sin(rotation);
+    let rotated_axis_x = axis_x * cos_rotation + axis_y * sin_rotation;
+    axis_y = axis_y * cos_rotation - axis_x * sin_rotation;
+    axis_x = rotated_axis_x;
+
     // Set the particle size
     var vpos = vertex_position;
     vpos = vpos * vec3<f32>(size.x, size.y, 1.0);
+    vpos = axis_x * vpos.x + axis_y * vpos.y;
 
     out.position = view.view_proj * vec4<f32>(particle.pos + vpos, 1.0);
     //out.color = vec4<f32>((vec4<u32>(vertex_color) >> vec4<u32>(0u, 8u, 16u, 24u)) & vec4<u32>(255u)) / 255.0;
@@ -86,8 +120,8 @@ fn vertex(
 [[stage(fragment)]]
 fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 #ifdef PARTICLE_TEXTURE
-    var color = textureSample(particle_texture, particle_sampler, in.uv);
-    color = vec4<f32>(1.0, 1.0, 1.0, color.r); // FIXME - grayscale modulate
+{{TEXTURE_SAMPLE_CODE}}
+{{TEXTURE_MODE_CODE}}
     color = in.color * color;
 #else
     var color = in.color;
@@ -3,6 +3,10 @@ struct Particle {
     age: f32;
     vel: vec3<f32>;
     lifetime: f32;
+    rotation: f32;
+    angular_velocity: f32;
+    random: f32;
+    __pad: f32;
 };
 
 struct ParticleAppearArea {    
@@ -23,7 +27,7 @@ struct ParticleAppearAreaBuffer{
 };
 
 struct ParticleBuffer {
-    particles: [[stride(32)]] array<Particle>;
+    particles: [[stride(48)]] array<Particle>;
 };
 
 struct SimParams {
@@ -49,7 +53,8 @@ struct Spawner {
     accel: vec3<f32>;
     count: atomic<i32>;
     force_field: array<ForceFieldParam, 16>;
-    __pad0: vec3<f32>;
+    __pad0: vec2<f32>;
+    spawn_offset: u32;
     seed: u32;
     __pad1: vec3<f32>;    
     live_time:f32;
@@ -59,6 +64,31 @@ struct IndirectBuffer {
     indices: [[stride(4)]] array<u32>;
 };
 
+struct MeshTriangle {
+    v0: vec3<f32>;
+    v1: vec3<f32>;
+    v2: vec3<f32>;
+    n0: vec3<f32>;
+    n1: vec3<f32>;
+    n2: vec3<f32>;
+};
+
+struct MeshTriangleBuffer {
+    triangles: [[stride(96)]] array<MeshTriangle>;
+};
+
+struct MeshAreaBuffer {
+    areas: [[stride(4)]] array<f32>;
+};
+
+struct DeathEventBuffer {
+    count: atomic<i32>;
+    spawn: atomic<i32>;
+    particles_per_event: i32;
+    __pad: u32;
+    events: [[stride(16)]] array<vec4<f32>>;
+};
+
 // var<uniform> values:array<array<array<i32>>>;
 
 [[group(0), binding(0)]] var<uniform> sim_params : SimParams;
@@ -66,8 +96,14 @@ struct IndirectBuffer {
 [[group(2), binding(0)]] var<storage, read_write> spawner : Spawner;
 [[group(3), binding(0)]] var<storage, read_write> indirect_buffer : IndirectBuffer;
 [[group(4), binding(0)]] var<storage, read> appear_area_buffer : ParticleAppearAreaBuffer;
+[[group(5), binding(0)]] var<storage, read_write> death_events_out : DeathEventBuffer;
+[[group(5), binding(1)]] var<storage, read_write> death_events_in : DeathEventBuffer;
+[[group(6), binding(0)]] var<storage, read> mesh_triangles : MeshTriangleBuffer;
+[[group(6), binding(1)]] var<storage, read> mesh_areas : MeshAreaBuffer;
 
 var<private> seed : u32 = 0u;
+var<private> spawn_origin : vec3<f32>;
+var<private> spawn_index : u32;
 
 let tau: f32 = 6.283185307179586476925286766559;
 
@@ -128,6 +164,88 @@ fn rand4(input: u32) -> vec4<f32> {
     return vec4<f32>(x, y, z, w);
 }
 
+// Hash of a point of the integer lattice of the noise functions
+fn lattice_hash(p: vec3<i32>, noise_seed: u32) -> u32 {
+    return pcg_hash(bitcast<u32>(p.x) ^ pcg_hash(bitcast<u32>(p.y) ^ pcg_hash(bitcast<u32>(p.z) ^ noise_seed)));
+}
+
+// Pseudo-random gradient in [-1:1]^3 of a point of the integer lattice
+fn lattice_gradient(p: vec3<i32>, noise_seed: u32) -> vec3<f32> {
+    let h = lattice_hash(p, noise_seed);
+    let g = vec3<u32>(h & 0x3ffu, (h >> 10u) & 0x3ffu, (h >> 20u) & 0x3ffu);
+    return vec3<f32>(g) / 511.5 - 1.;
+}
+
+// Contribution of the lattice corner at offset `o` of the cell containing the noise point
+fn gradient_corner(i: vec3<i32>, f: vec3<f32>, o: vec3<i32>, noise_seed: u32) -> f32 {
+    return dot(lattice_gradient(i + o, noise_seed), f - vec3<f32>(o));
+}
+
+// Perlin gradient noise, roughly in [-1:1]
+fn gradient_noise(p: vec3<f32>, noise_seed: u32) -> f32 {
+    let cell = floor(p);
+    let i = vec3<i32>(cell);
+    let f = p - cell;
+    // Quintic interpolation, for a continuous second derivative
+    let u = f * f * f * (f * (f * 6. - 15.) + 10.);
+    let n000 = gradient_corner(i, f, vec3<i32>(0, 0, 0), noise_seed);
+    let n100 = gradient_corner(i, f, vec3<i32>(1, 0, 0), noise_seed);
+    let n010 = gradient_corner(i, f, vec3<i32>(0, 1, 0), noise_seed);
+    let n110 = gradient_corner(i, f, vec3<i32>(1, 1, 0), noise_seed);
+    let n001 = gradient_corner(i, f, vec3<i32>(0, 0, 1), noise_seed);
+    let n101 = gradient_corner(i, f, vec3<i32>(1, 0, 1), noise_seed);
+    let n011 = gradient_corner(i, f, vec3<i32>(0, 1, 1), noise_seed);
+    let n111 = gradient_corner(i, f, vec3<i32>(1, 1, 1), noise_seed);
+    let nx00 = mix(n000, n100, u.x);
+    let nx10 = mix(n010, n110, u.x);
+    let nx01 = mix(n001, n101, u.x);
+    let nx11 = mix(n011, n111, u.x);
+    return mix(mix(nx00, nx10, u.y), mix(nx01, nx11, u.y), u.z);
+}
+
+// Vector potential of the curl noise, made of three independent noise fields
+fn noise_potential(p: vec3<f32>, noise_seed: u32) -> vec3<f32> {
+    return vec3<f32>(
+        gradient_noise(p, noise_seed),
+        gradient_noise(p, noise_seed + 1u),
+        gradient_noise(p, noise_seed + 2u)
+    );
+}
+
+// Divergence-free noise field, as the curl of the noise potential, evaluated by central
+// finite differences.
+fn curl_noise(p: vec3<f32>, noise_seed: u32) -> vec3<f32> {
+    let e = 0.01;
+    let dx = vec3<f32>(e, 0., 0.);
+    let dy = vec3<f32>(0., e, 0.);
+    let dz = vec3<f32>(0., 0., e);
+    let ddx = noise_potential(p + dx, noise_seed) - noise_potential(p - dx, noise_seed);
+    let ddy = noise_potential(p + dy, noise_seed) - noise_potential(p - dy, noise_seed);
+    let ddz = noise_potential(p + dz, noise_seed) - noise_potential(p - dz, noise_seed);
+    return vec3<f32>(ddy.z - ddz.y, ddz.x - ddx.z, ddx.y - ddy.x) / (2. * e);
+}
+
+// Pick a random mesh triangle with a probability proportional to its area, by binary search
+// into the table of cumulative triangle areas.
+fn sample_mesh_triangle() -> u32 {
+    let count = arrayLength(&mesh_areas.areas);
+    let target = rand() * mesh_areas.areas[count - 1u];
+    var lo = 0u;
+    var hi = count - 1u;
+    loop {
+        if (lo >= hi) {
+            break;
+        }
+        let mid = (lo + hi) / 2u;
+        if (mesh_areas.areas[mid] < target) {
+            lo = mid + 1u;
+        } else {
+            hi = mid;
+        }
+    }
+    return lo;
+}
+
 struct PosVel {
     pos: vec3<f32>;
     vel: vec3<f32>;
@@ -139,11 +257,30 @@ fn init_pos_vel(index: u32) -> PosVel {
 
 {{INIT_POS_VEL}}
 
+    // Velocity modifiers, in their own scope to not clash with the position code
+    {
+{{INIT_VELOCITY}}
+    }
+
     return ret;
 }
 
+fn try_spawn(appear_area_index: u32) -> bool {
+{{SPAWN_CODE}}
+}
+
 fn init_lifetime() -> f32 {
-    return spawner.live_time;
+{{INIT_LIFETIME}}
+}
+
+// Initial rotation and angular velocity of a newly emitted particle
+fn init_rotation() -> vec2<f32> {
+{{INIT_ROTATION}}
+}
+
+// Record the death of a particle at the given position for the sub-emitter of the effect, if any
+fn record_death_event(pos: vec3<f32>) {
+{{DEATH_EVENT_CODE}}
 }
 
 fn proj(u: vec3<f32>, v: vec3<f32>) -> vec3<f32> {
@@ -175,25 +312,38 @@ fn main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>) {
     var vVel : vec3<f32> = particle_buffer.particles[index].vel;
     var vAge : f32 = particle_buffer.particles[index].age;
     var vLifetime : f32 = particle_buffer.particles[index].lifetime;
+    var vRotation : f32 = particle_buffer.particles[index].rotation;
+    var vAngularVelocity : f32 = particle_buffer.particles[index].angular_velocity;
     var appear_area_pos: vec3<f32> = appear_area_buffer.particleAppearAreas[appear_area_index].position;
     var direction: vec3<f32> = appear_area_buffer.particleAppearAreas[appear_area_index].flow_direction;
     var speed: f32 = appear_area_buffer.particleAppearAreas[appear_area_index].flow_speed;
     // Age the particle
     vAge = vAge + sim_params.dt;
     if (vAge >= vLifetime) {
+        // Record the death of a particle still alive after the previous update; particles
+        // killed by the update modifiers already recorded theirs when killed.
+        if (particle_buffer.particles[index].age < vLifetime) {
+            record_death_event(vPos);
+            particle_buffer.particles[index].age = vAge;
+        }
+
         // Particle dead; try to recycle into newly-spawned one
-        if (atomicSub(&spawner.spawn, 1) > 0) {
+        if (try_spawn(appear_area_index)) {
             // Update PRNG seed
             seed = pcg_hash(index ^ spawner.seed);
 
             // Initialize new particle
             var posVel = init_pos_vel(appear_area_index);
   
-            vPos = (posVel.pos + appear_area_pos + spawner.origin);
+            vPos = posVel.pos + spawn_origin;
 
             vVel = posVel.vel;
             vAge = 0.0;
             vLifetime = init_lifetime();
+            let rotation = init_rotation();
+            vRotation = rotation.x;
+            vAngularVelocity = rotation.y;
+            particle_buffer.particles[index].random = rand();
         } else {
             // Nothing to spawn; simply return without writing any update
             return;
@@ -202,6 +352,21 @@ fn main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>) {
 
 {{FORCE_FIELD_CODE}}
 
+    vRotation = vRotation + vAngularVelocity * sim_params.dt;
+
+    // Particles killed by the update modifiers record their death now, and are recycled on the
+    // next update
+    if (vAge >= vLifetime) {
+        record_death_event(vPos);
+        particle_buffer.particles[index].pos = vPos;
+        particle_buffer.particles[index].vel = vVel;
+        particle_buffer.particles[index].age = vAge;
+        particle_buffer.particles[index].lifetime = vLifetime;
+        particle_buffer.particles[index].rotation = vRotation;
+        particle_buffer.particles[index].angular_velocity = vAngularVelocity;
+        return;
+    }
+
     // Increment alive particle count and write indirection index
     let indirect_index = atomicAdd(&spawner.count, 1);
     indirect_buffer.indices[indirect_index] = index;
@@ -211,4 +376,6 @@ fn main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>) {
     particle_buffer.particles[index].vel = vVel;
     particle_buffer.particles[index].age = vAge;
     particle_buffer.particles[index].lifetime = vLifetime;
+    particle_buffer.particles[index].rotation = vRotation;
+    particle_buffer.particles[index].angular_velocity = vAngularVelocity;
 }
\ No newline at end of file
@@ -0,0 +1,513 @@
+//! Minimal syntax tree of a RON document, to merge the fields of a document into another one.
+//!
+//! Unlike [`ron::Value`], the tree keeps the names of the enum variants, so the merged document
+//! can still be deserialized into the types it was serialized from.
+
+use ron::error::{Error, ErrorCode, Position};
+use std::fmt;
+
+/// A node of a RON document.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RonNode {
+    /// Number, string, character, boolean, unit variant, or any other value without any
+    /// nested value, kept verbatim.
+    Atom(String),
+    /// Struct or struct-like enum variant, with its named fields.
+    Struct {
+        /// Name of the struct or variant, if written.
+        ident: Option<String>,
+        /// Fields of the struct, in order.
+        fields: Vec<(String, RonNode)>,
+    },
+    /// Tuple, tuple struct, or tuple-like enum variant, including `Some(...)`.
+    Tuple {
+        /// Name of the tuple struct or variant, if written.
+        ident: Option<String>,
+        /// Elements of the tuple, in order.
+        items: Vec<RonNode>,
+    },
+    /// Sequence of values.
+    Seq(Vec<RonNode>),
+    /// Map of key-value pairs.
+    Map(Vec<(RonNode, RonNode)>),
+}
+
+impl RonNode {
+    /// Parse a RON document.
+    pub fn parse(s: &str) -> Result<RonNode, Error> {
+        let mut parser = Parser { s, pos: 0 };
+        parser.skip_ws()?;
+        // Skip the extension attributes, if any
+        while parser.rest().starts_with("#!") {
+            match parser.rest().find(']') {
+                Some(end) => parser.pos += end + 1,
+                None => return Err(parser.error("unterminated attribute")),
+            }
+            parser.skip_ws()?;
+        }
+        let node = parser.value()?;
+        parser.skip_ws()?;
+        if parser.pos < s.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(node)
+    }
+
+    /// Get the value of a field of a struct by name.
+    pub fn field(&self, name: &str) -> Option<&RonNode> {
+        match self {
+            RonNode::Struct { fields, .. } => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a field of a struct by name, for modification.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut RonNode> {
+        match self {
+            RonNode::Struct { fields, .. } => fields
+                .iter_mut()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Merge another node into this one.
+    ///
+    /// Structs merge field by field, recursively, keeping the fields absent from `other`. The
+    /// elements of tuples with the same name, like the content of an enum variant, merge one
+    /// by one. A value which isn't `Some(...)` or `None` merges into the content of a
+    /// `Some(...)`, to support the implicit `Some` extension. Maps merge key by key. Any other
+    /// value, including sequences, replaces the current one.
+    pub fn merge(&mut self, other: RonNode) {
+        match (self, other) {
+            (
+                RonNode::Struct { ident, fields },
+                RonNode::Struct {
+                    ident: other_ident,
+                    fields: other_fields,
+                },
+            ) if ident.is_none() || other_ident.is_none() || *ident == other_ident => {
+                for (name, value) in other_fields {
+                    match fields.iter_mut().find(|(field, _)| *field == name) {
+                        Some((_, field)) => field.merge(value),
+                        None => fields.push((name, value)),
+                    }
+                }
+            }
+            (
+                RonNode::Tuple {
+                    ident: Some(ident),
+                    items,
+                },
+                RonNode::Tuple {
+                    ident: Some(other_ident),
+                    items: other_items,
+                },
+            ) if *ident == other_ident && items.len() == other_items.len() => {
+                for (item, other_item) in items.iter_mut().zip(other_items) {
+                    item.merge(other_item);
+                }
+            }
+            (
+                RonNode::Tuple {
+                    ident: Some(ident),
+                    items,
+                },
+                other,
+            ) if ident == "Some" && items.len() == 1 && !other.is_option() => {
+                items[0].merge(other);
+            }
+            (RonNode::Map(entries), RonNode::Map(other_entries)) => {
+                for (key, value) in other_entries {
+                    match entries.iter_mut().find(|(entry, _)| *entry == key) {
+                        Some((_, entry)) => entry.merge(value),
+                        None => entries.push((key, value)),
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
+    /// Check if the node is an explicit `Some(...)` or `None`.
+    fn is_option(&self) -> bool {
+        match self {
+            RonNode::Atom(atom) => atom == "None",
+            RonNode::Tuple {
+                ident: Some(ident), ..
+            } => ident == "Some",
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RonNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonNode::Atom(atom) => f.write_str(atom),
+            RonNode::Struct { ident, fields } => {
+                write!(f, "{}(", ident.as_deref().unwrap_or(""))?;
+                for (name, value) in fields {
+                    write!(f, "{}:{},", name, value)?;
+                }
+                f.write_str(")")
+            }
+            RonNode::Tuple { ident, items } => {
+                write!(f, "{}(", ident.as_deref().unwrap_or(""))?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str(")")
+            }
+            RonNode::Seq(items) => {
+                f.write_str("[")?;
+                for item in items {
+                    write!(f, "{},", item)?;
+                }
+                f.write_str("]")
+            }
+            RonNode::Map(entries) => {
+                f.write_str("{")?;
+                for (key, value) in entries {
+                    write!(f, "{}:{},", key, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        let before = &self.s[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        Error {
+            code: ErrorCode::Message(message.to_string()),
+            position: Position { line, col },
+        }
+    }
+
+    /// Skip whitespaces and comments.
+    fn skip_ws(&mut self) -> Result<(), Error> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                // Block comments can be nested
+                let mut depth = 0;
+                loop {
+                    let rest = self.rest();
+                    if rest.starts_with("/*") {
+                        depth += 1;
+                        self.pos += 2;
+                    } else if rest.starts_with("*/") {
+                        depth -= 1;
+                        self.pos += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if let Some(c) = rest.chars().next() {
+                        self.pos += c.len_utf8();
+                    } else {
+                        return Err(self.error("unterminated block comment"));
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_ws()?;
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    /// Consume a comma separating two elements, if any, and check for the closing delimiter.
+    /// Returns `true` once the closing delimiter is consumed.
+    fn separator(&mut self, end: char) -> Result<bool, Error> {
+        self.skip_ws()?;
+        if self.peek() == Some(',') {
+            self.pos += 1;
+            self.skip_ws()?;
+        } else if self.peek() != Some(end) {
+            return Err(self.error(&format!("expected ',' or '{}'", end)));
+        }
+        if self.peek() == Some(end) {
+            self.pos += 1;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let raw = if rest.starts_with("r#") { 2 } else { 0 };
+        let mut chars = rest[raw..].char_indices();
+        match chars.next() {
+            Some((_, c)) if c.is_ascii_alphabetic() || c == '_' => {}
+            _ => return None,
+        }
+        let len = chars
+            .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '_'))
+            .map_or(rest.len() - raw, |(i, _)| i);
+        self.pos += raw + len;
+        Some(&rest[..raw + len])
+    }
+
+    fn value(&mut self) -> Result<RonNode, Error> {
+        self.skip_ws()?;
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => self.group(None),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.skip_ws()?;
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(RonNode::Seq(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.separator(']')? {
+                        return Ok(RonNode::Seq(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = vec![];
+                self.skip_ws()?;
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(RonNode::Map(entries));
+                }
+                loop {
+                    let key = self.value()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    if self.separator('}')? {
+                        return Ok(RonNode::Map(entries));
+                    }
+                }
+            }
+            Some('"') => {
+                self.string()?;
+                Ok(RonNode::Atom(self.s[start..self.pos].to_string()))
+            }
+            Some('r') if self.rest().starts_with("r\"") || self.rest().starts_with("r#\"") => {
+                self.raw_string()?;
+                Ok(RonNode::Atom(self.s[start..self.pos].to_string()))
+            }
+            Some('\'') => {
+                self.pos += 1;
+                loop {
+                    match self.peek() {
+                        Some('\\') => self.pos += 2,
+                        Some('\'') => break,
+                        Some(c) => self.pos += c.len_utf8(),
+                        None => return Err(self.error("unterminated character")),
+                    }
+                }
+                self.pos += 1;
+                Ok(RonNode::Atom(self.s[start..self.pos].to_string()))
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let len = self
+                    .rest()
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || "+-._".contains(c)))
+                    .unwrap_or(self.rest().len());
+                self.pos += len;
+                Ok(RonNode::Atom(self.s[start..self.pos].to_string()))
+            }
+            _ => match self.ident() {
+                Some(ident) => {
+                    let end = self.pos;
+                    self.skip_ws()?;
+                    if self.peek() == Some('(') {
+                        self.group(Some(ident.to_string()))
+                    } else {
+                        self.pos = end;
+                        Ok(RonNode::Atom(ident.to_string()))
+                    }
+                }
+                None => Err(self.error("expected a value")),
+            },
+        }
+    }
+
+    /// Parse a parenthesized struct or tuple, after its name if any.
+    fn group(&mut self, ident: Option<String>) -> Result<RonNode, Error> {
+        self.expect('(')?;
+        self.skip_ws()?;
+        if self.peek() == Some(')') {
+            self.pos += 1;
+            return Ok(RonNode::Tuple {
+                ident,
+                items: vec![],
+            });
+        }
+
+        // A struct starts with a field name followed by a colon
+        let start = self.pos;
+        let is_struct = self.ident().is_some() && {
+            self.skip_ws()?;
+            self.peek() == Some(':')
+        };
+        self.pos = start;
+
+        if is_struct {
+            let mut fields = vec![];
+            loop {
+                self.skip_ws()?;
+                let name = match self.ident() {
+                    Some(name) => name.to_string(),
+                    None => return Err(self.error("expected a field name")),
+                };
+                self.expect(':')?;
+                fields.push((name, self.value()?));
+                if self.separator(')')? {
+                    return Ok(RonNode::Struct { ident, fields });
+                }
+            }
+        } else {
+            let mut items = vec![];
+            loop {
+                items.push(self.value()?);
+                if self.separator(')')? {
+                    return Ok(RonNode::Tuple { ident, items });
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), Error> {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek() {
+                        self.pos += c.len_utf8();
+                    }
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(c) => self.pos += c.len_utf8(),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn raw_string(&mut self) -> Result<(), Error> {
+        self.pos += 1;
+        let hashes = self.rest().len() - self.rest().trim_start_matches('#').len();
+        self.pos += hashes + 1;
+        let end = format!("\"{}", "#".repeat(hashes));
+        match self.rest().find(&end) {
+            Some(len) => {
+                self.pos += len + end.len();
+                Ok(())
+            }
+            None => Err(self.error("unterminated raw string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_print() {
+        let s = r##"#![enable(implicit_some)]
+        // Comment
+        (
+            name: "a \"quoted\" (name)",
+            /* nested /* block */ comment */
+            value: Single(-1.5e3),
+            pos: (1.0, 2.0, 3.0),
+            unit: (),
+            list: [Surface, Volume],
+            map: { 0: 'c', r#"raw"#: None },
+        )"##;
+        let node = RonNode::parse(s).unwrap();
+        assert_eq!(
+            node.to_string(),
+            r##"(name:"a \"quoted\" (name)",value:Single(-1.5e3),pos:(1.0,2.0,3.0),unit:(),list:[Surface,Volume,],map:{0:'c',r#"raw"#:None,},)"##
+        );
+        assert_eq!(RonNode::parse(&node.to_string()).unwrap(), node);
+        assert_eq!(
+            node.field("value"),
+            Some(&RonNode::Tuple {
+                ident: Some("Single".to_string()),
+                items: vec![RonNode::Atom("-1.5e3".to_string())],
+            })
+        );
+
+        assert!(RonNode::parse("(a: 1,").is_err());
+        assert!(RonNode::parse("(a: 1) 2").is_err());
+        let err = RonNode::parse("(\n  a: ]").unwrap_err();
+        assert_eq!(err.position, Position { line: 2, col: 6 });
+    }
+
+    #[test]
+    fn merge() {
+        let mut node = RonNode::parse(
+            "(a: 1, b: (c: 2, d: Uniform((3, 4))), e: Some((f: 5, g: 6)), h: [7, 8], i: { 1: 9 })",
+        )
+        .unwrap();
+        node.merge(
+            RonNode::parse("(b: (d: Uniform((10, 4))), e: (g: 11), h: [12], i: { 2: 13 }, j: 14)")
+                .unwrap(),
+        );
+        assert_eq!(
+            node,
+            RonNode::parse(
+                "(a: 1, b: (c: 2, d: Uniform((10, 4))), e: Some((f: 5, g: 11)), h: [12], \
+                i: { 1: 9, 2: 13 }, j: 14)"
+            )
+            .unwrap()
+        );
+
+        // Different variants replace each other
+        let mut node = RonNode::parse("Uniform((1, 2))").unwrap();
+        node.merge(RonNode::parse("Single(3)").unwrap());
+        assert_eq!(node, RonNode::parse("Single(3)").unwrap());
+        let mut node = RonNode::parse("Some(1)").unwrap();
+        node.merge(RonNode::parse("None").unwrap());
+        assert_eq!(node, RonNode::Atom("None".to_string()));
+    }
+}
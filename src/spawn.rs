@@ -208,6 +208,11 @@ impl Spawner {
         self.active
     }
 
+    /// Gets the time between bursts of particles, in seconds.
+    pub fn period(&self) -> Value<f32> {
+        self.period
+    }
+
     /// Resamples the spawn time and period.
     fn resample(&mut self, rng: &mut Pcg32) {
         self.limit = self.period.sample(rng);
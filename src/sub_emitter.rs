@@ -0,0 +1,220 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{EffectAsset, ParticleEffect, ParticleEffectBundle};
+
+/// Component marking the effect instance of a sub-emitter.
+///
+/// Sub-emitter instances are spawned automatically as children of the entity holding the
+/// instance of an effect with a [`SubEmitterModifier`]. They don't use their own spawner to
+/// emit particles; instead they consume the death events of the particles of their parent
+/// instance.
+///
+/// [`SubEmitterModifier`]: crate::SubEmitterModifier
+#[derive(Debug, Clone, Component)]
+pub struct SubEmitterOf {
+    /// Entity of the parent effect instance whose particles spawn the ones of this instance.
+    pub parent: Entity,
+    /// Handle of the parent effect asset.
+    pub parent_handle: Handle<EffectAsset>,
+    /// Nesting depth of the sub-emitter; 1 for the direct sub-emitter of a regular effect.
+    pub depth: u32,
+}
+
+/// Maximum nesting depth of sub-emitters.
+///
+/// A chain of sub-emitters deeper than this, which generally denotes a cycle between effects
+/// (A spawns B which spawns A), is cut at that depth with a warning.
+pub const MAX_SUB_EMITTER_DEPTH: u32 = 4;
+
+/// Marker component for effect instances whose sub-emitter, if any, was already spawned.
+#[derive(Debug, Default, Clone, Component)]
+pub(crate) struct SubEmitterSpawned {
+    /// Sub-emitter instance spawned for the effect instance, and the effect it instantiates.
+    child: Option<(Entity, Handle<EffectAsset>)>,
+}
+
+/// Spawn the sub-emitter instance of all effect instances whose asset declares one.
+///
+/// Each effect instance is checked once its asset is loaded; instances of an effect without
+/// a sub-emitter are only marked as checked. Instances are checked again when their asset is
+/// modified, and their sub-emitter instance is despawned or replaced if the sub-emitter of the
+/// effect changed.
+pub(crate) fn spawn_sub_emitters(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<EffectAsset>>,
+    effects: Res<Assets<EffectAsset>>,
+    spawned_query: Query<(
+        Entity,
+        &ParticleEffect,
+        Option<&SubEmitterOf>,
+        &SubEmitterSpawned,
+    )>,
+    query: Query<(Entity, &ParticleEffect, Option<&SubEmitterOf>), Without<SubEmitterSpawned>>,
+) {
+    let modified: HashSet<Handle<EffectAsset>> = asset_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone_weak()),
+            _ => None,
+        })
+        .collect();
+    if !modified.is_empty() {
+        for (entity, effect, sub_emitter_of, spawned) in spawned_query.iter() {
+            if !modified.contains(&effect.handle) {
+                continue;
+            }
+            let handle = effects
+                .get(&effect.handle)
+                .and_then(|asset| asset.update_layout().sub_emitter)
+                .map(|(handle, _)| handle);
+            if handle.as_ref() == spawned.child.as_ref().map(|(_, handle)| handle) {
+                continue;
+            }
+            trace!(
+                "Sub-emitter of effect {:?} on entity {:?} changed to {:?}",
+                effect.handle,
+                entity,
+                handle
+            );
+            if let Some((child, _)) = spawned.child {
+                commands.entity(child).despawn_recursive();
+            }
+            let child = handle.and_then(|handle| {
+                spawn_sub_emitter(&mut commands, entity, effect, sub_emitter_of, handle)
+            });
+            commands.entity(entity).insert(SubEmitterSpawned { child });
+        }
+    }
+
+    for (entity, effect, sub_emitter_of) in query.iter() {
+        let asset = match effects.get(&effect.handle) {
+            Some(asset) => asset,
+            None => continue,
+        };
+        let child = asset.update_layout().sub_emitter.and_then(|(handle, _)| {
+            spawn_sub_emitter(&mut commands, entity, effect, sub_emitter_of, handle)
+        });
+        commands.entity(entity).insert(SubEmitterSpawned { child });
+    }
+}
+
+/// Spawn the instance of the sub-emitter `handle` of an effect instance, as a child of it.
+///
+/// Returns the child entity and the sub-emitter effect, or `None` if the sub-emitter cannot be
+/// spawned.
+fn spawn_sub_emitter(
+    commands: &mut Commands,
+    entity: Entity,
+    effect: &ParticleEffect,
+    sub_emitter_of: Option<&SubEmitterOf>,
+    handle: Handle<EffectAsset>,
+) -> Option<(Entity, Handle<EffectAsset>)> {
+    if handle == effect.handle {
+        warn!(
+            "Effect {:?} on entity {:?} uses itself as sub-emitter, ignoring it.",
+            effect.handle, entity
+        );
+        return None;
+    }
+    let depth = sub_emitter_of.map_or(0, |sub_emitter_of| sub_emitter_of.depth) + 1;
+    if depth > MAX_SUB_EMITTER_DEPTH {
+        warn!(
+            "Sub-emitter {:?} of effect {:?} on entity {:?} exceeds the maximum nesting \
+            depth of {}, not spawning it. Is there a cycle between sub-emitters?",
+            handle, effect.handle, entity, MAX_SUB_EMITTER_DEPTH
+        );
+        return None;
+    }
+    trace!(
+        "Spawning sub-emitter {:?} (depth {}) for effect {:?} on entity {:?}",
+        handle,
+        depth,
+        effect.handle,
+        entity
+    );
+    let child = commands
+        .spawn_bundle(ParticleEffectBundle::new(handle.clone()))
+        .insert(SubEmitterOf {
+            parent: entity,
+            parent_handle: effect.handle.clone_weak(),
+            depth,
+        })
+        .id();
+    commands.entity(entity).push_children(&[child]);
+    Some((child, handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SubEmitterModifier;
+    use bevy::asset::AssetPlugin;
+
+    fn sub_emitter(effect: Handle<EffectAsset>) -> SubEmitterModifier {
+        SubEmitterModifier {
+            effect,
+            particles_per_event: 1,
+            ..Default::default()
+        }
+    }
+
+    fn children(app: &mut App, parent: Entity) -> Vec<(Entity, Handle<EffectAsset>)> {
+        let mut query = app
+            .world
+            .query::<(Entity, &ParticleEffect, &SubEmitterOf)>();
+        query
+            .iter(&app.world)
+            .filter(|(_, _, sub_emitter_of)| sub_emitter_of.parent == parent)
+            .map(|(entity, effect, _)| (entity, effect.handle.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn reload_sub_emitter() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<EffectAsset>()
+            .add_system(spawn_sub_emitters);
+
+        let mut effects = app.world.resource_mut::<Assets<EffectAsset>>();
+        let sparks = effects.add(EffectAsset::default());
+        let smoke = effects.add(EffectAsset::default());
+        let rocket = effects.add(EffectAsset::default().update(sub_emitter(sparks.clone())));
+        let entity = app
+            .world
+            .spawn()
+            .insert(ParticleEffect::new(rocket.clone()))
+            .id();
+
+        app.update();
+        let sparks_children = children(&mut app, entity);
+        assert_eq!(sparks_children.len(), 1);
+        assert_eq!(sparks_children[0].1, sparks);
+
+        // Changing the sub-emitter replaces the child instance
+        let mut effects = app.world.resource_mut::<Assets<EffectAsset>>();
+        effects.get_mut(&rocket).unwrap().modifiers[0] = sub_emitter(smoke.clone()).into();
+        app.update();
+        app.update();
+        let smoke_children = children(&mut app, entity);
+        assert_eq!(smoke_children.len(), 1);
+        assert_eq!(smoke_children[0].1, smoke);
+        assert!(app.world.get_entity(sparks_children[0].0).is_none());
+
+        // Modifying the effect without changing its sub-emitter keeps the child instance
+        let mut effects = app.world.resource_mut::<Assets<EffectAsset>>();
+        effects.get_mut(&rocket).unwrap().capacity = 32;
+        app.update();
+        app.update();
+        assert_eq!(children(&mut app, entity), smoke_children);
+
+        // Removing the sub-emitter despawns the child instance
+        let mut effects = app.world.resource_mut::<Assets<EffectAsset>>();
+        effects.get_mut(&rocket).unwrap().modifiers.clear();
+        app.update();
+        app.update();
+        assert!(children(&mut app, entity).is_empty());
+        assert!(app.world.get_entity(smoke_children[0].0).is_none());
+    }
+}